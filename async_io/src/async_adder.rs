@@ -6,18 +6,19 @@ use tokio::net::TcpListener;
 use tokio::sync::watch as Channel_type;
 use tokio::task::JoinHandle;
 
+use crate::cluster::{ClusterConfig, spawn_cluster};
 use crate::ctrl_c_waiter::CtrlCWaiter;
-use crate::state::State;
+use crate::event::Event;
+use crate::state::{Number, State};
 use crate::stdio::Stdio;
+use crate::websocket;
 
 #[cfg(test)]
 use mockall::mock;
 
-async fn read_int<Reader, Buff>(
-    socket: &mut Reader,
-    buf: &mut Buff,
-) -> Result<usize, std::io::Error>
+async fn read_int<N, Reader, Buff>(socket: &mut Reader, buf: &mut Buff) -> Result<N, std::io::Error>
 where
+    N: Number,
     Reader: AsyncReadExt + Unpin,
     Buff: BufMut + Buf + Send,
 {
@@ -29,28 +30,30 @@ where
         // check for \n character
         let parsed_string = std::str::from_utf8(buf.chunk()).unwrap();
         if let Some(pos) = parsed_string.find('\n') {
-            let x: usize = parsed_string[0..pos].trim().parse().unwrap();
+            let x = parsed_string[0..pos].trim().parse::<N>();
             buf.advance(pos + 1);
-            return Ok(x);
+            return x.map_err(|e| std::io::Error::new(ErrorKind::InvalidData, e));
         }
     }
 }
 
-struct Connection<Socket>
+struct Connection<Socket, N>
 where
     Socket: AsyncReadExt + AsyncWriteExt + Unpin + Send + 'static,
+    N: Number,
 {
     buf: BytesMut,
-    task_state: State,
-    event_receiver: Channel_type::Receiver<String>,
+    task_state: State<N>,
+    event_receiver: Channel_type::Receiver<Event>,
     socket: BufStream<Socket>,
 }
 
-impl<Socket> Connection<Socket>
+impl<Socket, N> Connection<Socket, N>
 where
     Socket: AsyncReadExt + AsyncWriteExt + Unpin + Send + 'static,
+    N: Number,
 {
-    fn new(task_state: State, socket: Socket) -> Connection<Socket> {
+    fn new(task_state: State<N>, socket: Socket) -> Connection<Socket, N> {
         let event_receiver = task_state.get_event_update_receiver();
         Connection {
             buf: BytesMut::with_capacity(10),
@@ -60,19 +63,14 @@ where
         }
     }
 
-    async fn read_int_and_watch_for_event(&mut self) -> Result<usize, std::io::Error> {
+    async fn read_int_and_watch_for_event(&mut self) -> Result<N, std::io::Error> {
         let n;
         loop {
             tokio::select! {
                 x = read_int(&mut self.socket, &mut self.buf) => {n=x?; break;},
                 _ = self.event_receiver.changed() => {
-                    let event_payload = format!(
-                        "\n got event: {}\n",
-                        *self.event_receiver.borrow_and_update()
-                    );
-                    self.socket
-                        .write_all(event_payload.as_bytes())
-                        .await?;
+                    let event_frame = self.event_receiver.borrow_and_update().encode();
+                    self.socket.write_all(&event_frame).await?;
                     self.socket.flush().await?;
                 }
             };
@@ -105,11 +103,12 @@ where
     }
 }
 
-fn create_new_connection_handler<Socket>(
-    le_state: State,
+fn create_new_connection_handler<Socket, N>(
+    le_state: State<N>,
 ) -> impl Fn(Socket) -> JoinHandle<Result<(), std::io::Error>>
 where
     Socket: AsyncReadExt + AsyncWriteExt + Unpin + Send + 'static,
+    N: Number,
 {
     move |socket| {
         let mut task_state = le_state.clone();
@@ -166,30 +165,90 @@ mock! {
     }
 }
 
-fn io_thread_main(thread_state: &mut State, stdio: &dyn Stdio) -> io::Result<()> {
+// A line of the form "<<DELIMITER" starts a heredoc, all following lines up
+// to a line containing only DELIMITER become one multi-line event.
+fn read_event(stdio: &dyn Stdio, buffer: &mut String) -> io::Result<Event> {
+    stdio.read_line(buffer)?;
+    let payload = match buffer.trim().strip_prefix("<<") {
+        Some(delimiter) if !delimiter.trim().is_empty() => {
+            let delimiter = delimiter.trim().to_string();
+            let mut lines = Vec::new();
+            loop {
+                buffer.clear();
+                if 0 == stdio.read_line(buffer)? {
+                    break;
+                }
+                let line = buffer.trim_end_matches(['\r', '\n']);
+                if line == delimiter {
+                    break;
+                }
+                lines.push(line.to_string());
+            }
+            lines.join("\n")
+        }
+        _ => buffer.trim().to_string(),
+    };
+    buffer.clear();
+    Event::new("message", Some("console"), payload)
+}
+
+fn io_thread_main<N: Number>(thread_state: &mut State<N>, stdio: &dyn Stdio) -> io::Result<()> {
     let mut buffer = String::new();
     buffer.reserve(10);
     loop {
         stdio.print("Enter event content: ")?;
         stdio.flush()?;
-        stdio.read_line(&mut buffer)?;
+        let event = read_event(stdio, &mut buffer)?;
 
-        let _ = thread_state.send_event(buffer.trim());
-        buffer.clear();
+        let _ = thread_state.send_event(event);
     }
 }
 
-pub async fn main2<Listener>(
+// Each WebSocket session runs a normal Connection on one end of a message
+// pipe, the other end is bridged to the WebSocket frames.
+fn spawn_websocket_gateway<N: Number>(listener: TcpListener, le_state: State<N>) {
+    let handle_new_connection = std::sync::Arc::new(create_new_connection_handler(le_state));
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut socket, _)) = listener.accept().await else {
+                continue;
+            };
+            let handle_new_connection = handle_new_connection.clone();
+            tokio::spawn(async move {
+                let mut buf = BytesMut::with_capacity(1024);
+                websocket::handshake(&mut socket, &mut buf).await?;
+                let (connection_end, gateway_end) = websocket::message_pipe();
+                let connection = handle_new_connection(connection_end);
+                let result = websocket::bridge(socket, buf, gateway_end).await;
+                connection.abort();
+                result
+            });
+        }
+    });
+}
+
+/// Optional parts of the server, all of them are off by default.
+#[derive(Default)]
+pub struct ServerOptions {
+    /// serves the adder sessions to browsers as well
+    pub websocket_listener: Option<TcpListener>,
+    /// shares the event feed with other servers
+    pub cluster: Option<ClusterConfig>,
+}
+
+pub async fn main2<N, Listener>(
     listener: Listener,
+    options: ServerOptions,
     ctrl_c_waiter: &impl CtrlCWaiter,
     stdio: Box<dyn Stdio + Send>,
 ) -> Result<(), Box<dyn std::error::Error>>
 where
+    N: Number,
     Listener: MyTcpListener + Send + 'static,
 {
     println!("listening on {}", listener.local_addr()?);
 
-    let le_state = State::default();
+    let le_state = State::<N>::default();
 
     // send event from user io thread, cannot be managed by tokio, because
     // reading from stdin blocks. Due to that the runtime will not shutdown
@@ -200,6 +259,19 @@ where
         let _ = io_thread_main(&mut thread_state, stdio.as_ref());
     });
 
+    if let Some(websocket_listener) = options.websocket_listener {
+        println!(
+            "websocket gateway listening on {}",
+            websocket_listener.local_addr()?
+        );
+        spawn_websocket_gateway(websocket_listener, le_state.clone());
+    }
+
+    // peer links stay up until the cluster is dropped at the end of main2
+    let _cluster = options
+        .cluster
+        .map(|config| spawn_cluster(config, le_state.clone()));
+
     let handle_new_connection = create_new_connection_handler(le_state);
 
     tokio::spawn(async move {
@@ -220,6 +292,7 @@ where
 #[cfg(test)]
 mod test {
     use std::{
+        collections::VecDeque,
         io::{self, ErrorKind, Read, Write},
         mem::swap,
         net::{SocketAddr, TcpStream},
@@ -230,9 +303,15 @@ mod test {
         time::Duration,
     };
 
-    use crate::async_adder::{MockMyTcpListenerMock, State, create_new_connection_handler, main2};
+    use crate::async_adder::{
+        MockMyTcpListenerMock, ServerOptions, State, create_new_connection_handler, main2,
+        read_event,
+    };
+    use crate::big_int::BigInt;
     use crate::ctrl_c_waiter::MockAsyncMockCtrlWaiter;
+    use crate::event::Event;
     use crate::stdio::MockStdio;
+    use crate::websocket::{OPCODE_TEXT, test_client::TestClient};
     use mockall::predicate::eq;
     use tokio::{
         net::TcpListener,
@@ -298,7 +377,7 @@ mod test {
 
     #[tokio::test]
     async fn test_return_connection_aborted() {
-        let task_state = State::default();
+        let task_state = State::<usize>::default();
         let socket = Builder::new().write(b"< x = ").build();
         let join_result = create_new_connection_handler(task_state)(socket).await;
         assert!(join_result.is_ok());
@@ -310,7 +389,7 @@ mod test {
 
     #[tokio::test]
     async fn test_newline_triggers_number_parsing() {
-        let task_state = State::default();
+        let task_state = State::<usize>::default();
         let socket = Builder::new()
             .write(b"< x = ")
             .read(b"3")
@@ -330,6 +409,108 @@ mod test {
         );
     }
 
+    #[tokio::test]
+    async fn test_big_int_sum_exceeds_usize() {
+        let task_state = State::<BigInt>::default();
+        let socket = Builder::new()
+            .write(b"< x = ")
+            .read(b"-123456789012345678901234567890\n")
+            .write(b"< y = ")
+            .read(b"123456789012345678901234567891000\n")
+            .write(b"> z = 123333332223333333222333333323110\n")
+            .write(b"< x = ")
+            .build();
+        assert!(
+            create_new_connection_handler(task_state)(socket)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_usize_overflowing_input_is_invalid_data() {
+        let task_state = State::<usize>::default();
+        let socket = Builder::new()
+            .write(b"< x = ")
+            .read(b"123456789012345678901234567890\n")
+            .build();
+        let error = create_new_connection_handler(task_state)(socket)
+            .await
+            .unwrap()
+            .unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+    }
+
+    fn create_line_reader_mock(lines: &[&str]) -> MockStdio {
+        let mut lines: VecDeque<String> = lines.iter().map(|l| l.to_string()).collect();
+        let mut stdio_mock = MockStdio::new();
+        stdio_mock
+            .expect_read_line()
+            .returning(move |buf: &mut String| match lines.pop_front() {
+                Some(line) => {
+                    buf.push_str(&line);
+                    Ok(line.len())
+                }
+                None => Ok(0),
+            });
+        stdio_mock
+    }
+
+    #[test]
+    fn test_read_single_line_event() {
+        let stdio_mock = create_line_reader_mock(&["  blub \n"]);
+        let mut buffer = String::new();
+        let event = read_event(&stdio_mock, &mut buffer).unwrap();
+        assert_eq!(b"blub", event.payload());
+        assert_eq!("message", event.kind());
+        assert_eq!(Some("console"), event.sender());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_read_heredoc_event() {
+        let stdio_mock =
+            create_line_reader_mock(&["<<END\n", "line 1\n", "  line 2\r\n", "END\n", "next\n"]);
+        let mut buffer = String::new();
+        let event = read_event(&stdio_mock, &mut buffer).unwrap();
+        assert_eq!(b"line 1\n  line 2", event.payload());
+        let event = read_event(&stdio_mock, &mut buffer).unwrap();
+        assert_eq!(b"next", event.payload());
+    }
+
+    #[test]
+    fn test_read_heredoc_event_ends_at_eof() {
+        let stdio_mock = create_line_reader_mock(&["<< EOF\n", "line 1\n", "line 2\n"]);
+        let mut buffer = String::new();
+        let event = read_event(&stdio_mock, &mut buffer).unwrap();
+        assert_eq!(b"line 1\nline 2", event.payload());
+    }
+
+    #[tokio::test]
+    async fn test_multi_line_event_is_one_frame() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        async fn expect(client: &mut tokio::io::DuplexStream, expected: &[u8]) {
+            let mut received = vec![0; expected.len()];
+            client.read_exact(&mut received).await.unwrap();
+            assert_eq!(expected, &received[..]);
+        }
+
+        let task_state = State::<usize>::default();
+        let event = Event::new("message", Some("console"), "a\n< y = \nb").unwrap();
+        let (socket, mut client) = tokio::io::duplex(64);
+        let handle = create_new_connection_handler(task_state.clone())(socket);
+        expect(&mut client, b"< x = ").await;
+        task_state.send_event(event).unwrap();
+        // x is only typed once the whole frame arrived
+        expect(&mut client, b"\n! message console 10\na\n< y = \nb\n").await;
+        client.write_all(b"1\n").await.unwrap();
+        expect(&mut client, b"< y = ").await;
+        drop(client);
+        let error = handle.await.unwrap().unwrap_err();
+        assert_eq!(ErrorKind::ConnectionAborted, error.kind());
+    }
+
     #[tokio::test]
     async fn test_main_terminates_when_ctrl_pressed() {
         let (ctrl_c_mock, terminate_main2) = create_ctrl_c_mock();
@@ -341,7 +522,13 @@ mod test {
 
         let mut listener_mock = create_listener_mock();
         setup_last_accept(&mut listener_mock, terminate_main2);
-        let _mr = main2(listener_mock, &ctrl_c_mock, stdio_mock).await;
+        let _mr = main2::<usize, _>(
+            listener_mock,
+            ServerOptions::default(),
+            &ctrl_c_mock,
+            stdio_mock,
+        )
+        .await;
         assert!(_mr.is_ok());
     }
 
@@ -359,7 +546,13 @@ mod test {
 
         setup_last_accept(&mut listener_mock, terminate_main2);
         let (stdio_mock, tx2) = create_blocked_io_mock();
-        let _mr = main2(listener_mock, &ctrl_c_mock, stdio_mock).await;
+        let _mr = main2::<usize, _>(
+            listener_mock,
+            ServerOptions::default(),
+            &ctrl_c_mock,
+            stdio_mock,
+        )
+        .await;
         tx2.send(()).unwrap();
         _mr.unwrap();
     }
@@ -375,7 +568,13 @@ mod test {
 
         setup_last_accept(&mut listener_mock, terminate_main2);
         let (stdio_mock, tx2) = create_blocked_io_mock();
-        let _mr = main2(listener_mock, &ctrl_c_mock, stdio_mock).await;
+        let _mr = main2::<usize, _>(
+            listener_mock,
+            ServerOptions::default(),
+            &ctrl_c_mock,
+            stdio_mock,
+        )
+        .await;
         tx2.send(()).unwrap();
         _mr.unwrap();
     }
@@ -410,7 +609,8 @@ mod test {
                 rx.recv().unwrap();
                 Err(io::Error::new(io::ErrorKind::BrokenPipe, ""))
             });
-        let _mr = main2(listener, &ctrl_c_mock, stdio_mock).await;
+        let _mr =
+            main2::<usize, _>(listener, ServerOptions::default(), &ctrl_c_mock, stdio_mock).await;
         tx.send(()).unwrap();
         _mr.unwrap();
         let result = response.join().unwrap();
@@ -425,11 +625,11 @@ mod test {
         let local_address = listener.local_addr().unwrap();
         let response = thread::spawn(move || {
             let mut to_server = TcpStream::connect(local_address).unwrap();
-            let mut buf = [0; 20];
+            let mut buf = [0; 30];
             to_server.read_exact(&mut buf[0..6]).unwrap();
             set_connected.send(()).unwrap();
             assert_eq!("< x = ", std::str::from_utf8(&buf[0..6]).unwrap());
-            to_server.read_exact(&mut buf[0..18]).unwrap();
+            to_server.read_exact(&mut buf[0..26]).unwrap();
             tx.send(()).unwrap();
             buf
         });
@@ -459,13 +659,85 @@ mod test {
                 rx.recv().unwrap();
                 Err(io::Error::new(io::ErrorKind::BrokenPipe, ""))
             });
-        let _mr = main2(listener, &ctrl_c_mock, stdio_mock).await;
+        let _mr =
+            main2::<usize, _>(listener, ServerOptions::default(), &ctrl_c_mock, stdio_mock).await;
         tx.send(()).unwrap();
         _mr.unwrap();
         let result = response.join().unwrap();
         assert_eq!(
-            "\n got event: blub\n",
-            std::str::from_utf8(&result[0..18]).unwrap()
+            "\n! message console 4\nblub\n",
+            std::str::from_utf8(&result[0..26]).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_main_serves_websocket_clients() {
+        let (ctrl_c_mock, tx) = create_ctrl_c_mock();
+        let (set_connected, is_connected) = mpsc::channel();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let websocket_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let websocket_address = websocket_listener.local_addr().unwrap();
+        let options = ServerOptions {
+            websocket_listener: Some(websocket_listener),
+            ..Default::default()
+        };
+        let client = tokio::spawn(async move {
+            let mut client = TestClient::connect(websocket_address).await.unwrap();
+            let mut messages = Vec::new();
+            messages.push(client.receive().await.unwrap());
+            set_connected.send(()).unwrap();
+            messages.push(client.receive().await.unwrap());
+            client.send(OPCODE_TEXT, b"20\n").await.unwrap();
+            messages.push(client.receive().await.unwrap());
+            client.send(OPCODE_TEXT, b"22\n").await.unwrap();
+            messages.push(client.receive().await.unwrap());
+            tx.send(()).unwrap();
+            messages
+        });
+        let mut stdio_mock = Box::new(MockStdio::new());
+        stdio_mock
+            .expect_print()
+            .once()
+            .with(eq("Enter event content: "))
+            .returning(|_a| Ok(0));
+        stdio_mock.expect_flush().once().returning(|| Ok(0));
+        stdio_mock
+            .expect_read_line()
+            .once()
+            .returning(move |buf: &mut String| {
+                is_connected.recv().unwrap();
+                buf.push_str("blub");
+                Ok(buf.len())
+            });
+        let (tx, rx) = mpsc::channel();
+        stdio_mock
+            .expect_print()
+            .once()
+            .with(eq("Enter event content: "))
+            .returning(move |_| {
+                rx.recv().unwrap();
+                Err(io::Error::new(io::ErrorKind::BrokenPipe, ""))
+            });
+        let _mr = main2::<usize, _>(listener, options, &ctrl_c_mock, stdio_mock).await;
+        tx.send(()).unwrap();
+        _mr.unwrap();
+        let messages: Vec<_> = client
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|frame| {
+                assert_eq!(OPCODE_TEXT, frame.opcode);
+                String::from_utf8(frame.payload).unwrap()
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                "< x = ",
+                "\n! message console 4\nblub\n",
+                "< y = ",
+                "> z = 42\n"
+            ],
+            messages
         );
     }
 
@@ -479,7 +751,7 @@ mod test {
                 println!("expected accept called");
                 let socket_mock = Builder::new()
                     .write(b"< x = ")
-                    .write(b"\n got event: blub\n")
+                    .write(b"\n! message console 4\nblub\n")
                     .build();
                 Ok((socket_mock, SocketAddr::from_str("127.0.0.1:1234").unwrap()))
             })
@@ -523,7 +795,13 @@ mod test {
                 Err(io::Error::new(io::ErrorKind::BrokenPipe, ""))
             });
 
-        let _mr = main2(listener_mock, &ctrl_c_mock, stdio_mock).await;
+        let _mr = main2::<usize, _>(
+            listener_mock,
+            ServerOptions::default(),
+            &ctrl_c_mock,
+            stdio_mock,
+        )
+        .await;
         _mr.unwrap();
     }
 }
@@ -0,0 +1,238 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::ops::{Add, Neg, Sub};
+use std::str::FromStr;
+
+// every limb holds 9 decimal digits, which keeps parsing and printing trivial
+const LIMB_BASE: u32 = 1_000_000_000;
+const LIMB_DIGITS: usize = 9;
+
+/// Signed integer of arbitrary size. The magnitude is stored little endian in
+/// limbs of base 10^9 without leading zero limbs, so zero has no limbs and is
+/// never negative.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    limbs: Vec<u32>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseBigIntError {}
+
+impl Display for ParseBigIntError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid digit found in string")
+    }
+}
+
+impl std::error::Error for ParseBigIntError {}
+
+impl BigInt {
+    fn from_parts(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        while limbs.last() == Some(&0) {
+            limbs.pop();
+        }
+        let negative = negative && !limbs.is_empty();
+        BigInt { negative, limbs }
+    }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (longer, shorter) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(longer.len() + 1);
+    let mut carry = 0;
+    for (i, limb) in longer.iter().enumerate() {
+        let sum = limb + shorter.get(i).unwrap_or(&0) + carry;
+        carry = sum / LIMB_BASE;
+        result.push(sum % LIMB_BASE);
+    }
+    if carry != 0 {
+        result.push(carry);
+    }
+    result
+}
+
+// a must not be smaller than b
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, limb) in a.iter().enumerate() {
+        let subtrahend = b.get(i).unwrap_or(&0) + borrow;
+        if *limb >= subtrahend {
+            result.push(limb - subtrahend);
+            borrow = 0;
+        } else {
+            result.push(limb + LIMB_BASE - subtrahend);
+            borrow = 1;
+        }
+    }
+    result
+}
+
+impl Add for BigInt {
+    type Output = BigInt;
+
+    fn add(self, rhs: BigInt) -> BigInt {
+        if self.negative == rhs.negative {
+            return BigInt::from_parts(self.negative, add_magnitude(&self.limbs, &rhs.limbs));
+        }
+        match cmp_magnitude(&self.limbs, &rhs.limbs) {
+            Ordering::Less => {
+                BigInt::from_parts(rhs.negative, sub_magnitude(&rhs.limbs, &self.limbs))
+            }
+            _ => BigInt::from_parts(self.negative, sub_magnitude(&self.limbs, &rhs.limbs)),
+        }
+    }
+}
+
+impl Neg for BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.limbs)
+    }
+}
+
+impl Sub for BigInt {
+    type Output = BigInt;
+
+    fn sub(self, rhs: BigInt) -> BigInt {
+        self + (-rhs)
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseBigIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.as_bytes().first() {
+            Some(b'-') => (true, &s[1..]),
+            Some(b'+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+            return Err(ParseBigIntError {});
+        }
+        let limbs = digits
+            .as_bytes()
+            .rchunks(LIMB_DIGITS)
+            .map(|chunk| {
+                chunk
+                    .iter()
+                    .fold(0, |limb, digit| limb * 10 + u32::from(digit - b'0'))
+            })
+            .collect();
+        Ok(BigInt::from_parts(negative, limbs))
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+        match self.limbs.split_last() {
+            None => write!(f, "0"),
+            Some((most_significant, rest)) => {
+                write!(f, "{most_significant}")?;
+                for limb in rest.iter().rev() {
+                    write!(f, "{limb:0width$}", width = LIMB_DIGITS)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BigInt, ParseBigIntError};
+
+    fn b(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_and_display_round_trip() {
+        for s in [
+            "0",
+            "7",
+            "-7",
+            "1000000000",
+            "123456789012345678901234567890",
+            "-98765432109876543210",
+        ] {
+            assert_eq!(s, b(s).to_string());
+        }
+    }
+
+    #[test]
+    fn parse_normalizes() {
+        assert_eq!("123", b("000000000000000123").to_string());
+        assert_eq!("42", b("+42").to_string());
+        assert_eq!("0", b("-0").to_string());
+        assert_eq!(BigInt::default(), b("-000"));
+        assert!(!b("-0").negative);
+    }
+
+    #[test]
+    fn parse_rejects_garbage() {
+        for s in ["", "-", "+", "12a", "--1", " 1", "1.0"] {
+            assert_eq!(Err(ParseBigIntError {}), s.parse::<BigInt>());
+        }
+    }
+
+    #[test]
+    fn carry_across_many_limbs() {
+        let nines = "9".repeat(100);
+        let expected = format!("1{}", "0".repeat(100));
+        assert_eq!(expected, (b(&nines) + b("1")).to_string());
+        assert_eq!(expected, (b("1") + b(&nines)).to_string());
+    }
+
+    #[test]
+    fn borrow_across_many_limbs() {
+        let power = format!("1{}", "0".repeat(100));
+        assert_eq!("9".repeat(100), (b(&power) - b("1")).to_string());
+        assert_eq!("9".repeat(100), (b(&power) + b("-1")).to_string());
+    }
+
+    #[test]
+    fn exceeds_usize() {
+        let max = usize::MAX.to_string();
+        assert_eq!("36893488147419103230", (b(&max) + b(&max)).to_string());
+    }
+
+    #[test]
+    fn negative_numbers() {
+        assert_eq!("-2", (b("-5") + b("3")).to_string());
+        assert_eq!("2", (b("5") + b("-3")).to_string());
+        assert_eq!("-8", (b("-5") + b("-3")).to_string());
+        assert_eq!("-2", (b("3") - b("5")).to_string());
+        assert_eq!("8", (b("5") - b("-3")).to_string());
+        assert_eq!(
+            "-1000000000000000000000",
+            (b("-999999999999999999999") + b("-1")).to_string()
+        );
+    }
+
+    #[test]
+    fn opposite_numbers_sum_to_zero() {
+        let sum = b("123456789123456789") + b("-123456789123456789");
+        assert_eq!(BigInt::default(), sum);
+        assert!(!sum.negative);
+        assert_eq!("0", sum.to_string());
+    }
+
+    #[test]
+    fn negation() {
+        assert_eq!(b("-12345678901234567890"), -b("12345678901234567890"));
+        assert_eq!(b("0"), -b("0"));
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::Duration;
+
+use async_io::big_int::BigInt;
+use async_io::loadgen::{LoadConfig, run_with_local_server};
+
+const USAGE: &str = "usage: loadgen [--connections N] [--rounds M] [--think-time-ms T] \
+                     [--event-interval-ms I] [--big-int]
+
+  --connections N        concurrent adder sessions (default 10)
+  --rounds M             x/y rounds per session (default 100)
+  --think-time-ms T      pause before each round (default 0)
+  --event-interval-ms I  inject a broadcast event every I ms, 0 disables (default 10)
+  --big-int              run the server in big-integer mode";
+
+fn parse_args() -> Result<(LoadConfig, bool), String> {
+    let mut config = LoadConfig::default();
+    let mut big_int = false;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--big-int" {
+            big_int = true;
+            continue;
+        }
+        let value: u64 = args
+            .next()
+            .ok_or(format!("missing value for {arg}"))?
+            .parse()
+            .map_err(|e| format!("invalid value for {arg}: {e}"))?;
+        match arg.as_str() {
+            "--connections" => config.connections = value as usize,
+            "--rounds" => config.rounds = value as usize,
+            "--think-time-ms" => config.think_time = Duration::from_millis(value),
+            "--event-interval-ms" => {
+                config.event_interval = (value != 0).then(|| Duration::from_millis(value))
+            }
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+    Ok((config, big_int))
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let (config, big_int) = parse_args().map_err(|e| format!("{e}\n{USAGE}"))?;
+    let report = if big_int {
+        run_with_local_server::<BigInt>(config).await?
+    } else {
+        run_with_local_server::<usize>(config).await?
+    };
+    println!("{report}");
+    Ok(())
+}
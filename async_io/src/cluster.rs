@@ -0,0 +1,608 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::event::{Event, EventOrigin, MAX_PAYLOAD_SIZE, is_token};
+use crate::state::{Number, State};
+
+// an event travels over at most this many peer links
+const DEFAULT_TTL: u8 = 16;
+// number of remembered event ids, older ids are forgotten first
+const SEEN_CAPACITY: usize = 4096;
+const MAX_HELLO_SIZE: usize = 256;
+const MAX_PEER_HEADER_SIZE: usize = 256;
+// a peer header followed by an event frame with the largest payload
+const MAX_MESSAGE_SIZE: usize = MAX_PEER_HEADER_SIZE + MAX_PAYLOAD_SIZE + 1024;
+
+pub struct ClusterConfig {
+    node_id: String,
+    /// accepts links from other servers
+    pub listener: Option<TcpListener>,
+    /// servers this one keeps a link to
+    pub peers: Vec<SocketAddr>,
+    pub min_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl ClusterConfig {
+    /// The node id is written into the peer headers, so it must be a single
+    /// word of visible ASCII characters.
+    pub fn new(node_id: &str) -> io::Result<ClusterConfig> {
+        if !is_token(node_id) {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "invalid node id"));
+        }
+        Ok(ClusterConfig {
+            node_id: node_id.to_string(),
+            listener: None,
+            peers: Vec::new(),
+            min_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(5),
+        })
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+}
+
+// Peer links carry
+//
+//   @ <origin> <seq> <ttl>\n<event frame>
+//
+// The origin names the server where the event was typed, together with the
+// sequence number it identifies the event in the whole cluster.
+#[derive(Debug, PartialEq, Eq)]
+struct PeerMessage {
+    origin: String,
+    seq: u64,
+    ttl: u8,
+    event: Event,
+}
+
+fn invalid_message(reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid peer message: {reason}"),
+    )
+}
+
+impl PeerMessage {
+    fn encode(&self) -> Vec<u8> {
+        let mut message = format!("@ {} {} {}\n", self.origin, self.seq, self.ttl).into_bytes();
+        message.extend_from_slice(&self.event.encode());
+        message
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Option<(PeerMessage, usize)>> {
+        let Some(header_len) = buf.iter().position(|c| *c == b'\n') else {
+            if buf.len() > MAX_PEER_HEADER_SIZE {
+                return Err(invalid_message("header too large"));
+            }
+            return Ok(None);
+        };
+        if header_len > MAX_PEER_HEADER_SIZE {
+            return Err(invalid_message("header too large"));
+        }
+        let header = std::str::from_utf8(&buf[..header_len])
+            .map_err(|_| invalid_message("header is not UTF-8"))?;
+        let ["@", origin, seq, ttl] = header.split(' ').collect::<Vec<_>>()[..] else {
+            return Err(invalid_message("header needs origin, seq and ttl"));
+        };
+        let seq = seq.parse().map_err(|_| invalid_message("invalid seq"))?;
+        let ttl = ttl.parse().map_err(|_| invalid_message("invalid ttl"))?;
+        let Some((event, event_len)) = Event::decode(&buf[header_len + 1..])? else {
+            if buf.len() > MAX_MESSAGE_SIZE {
+                return Err(invalid_message("message too large"));
+            }
+            return Ok(None);
+        };
+        let message = PeerMessage {
+            origin: origin.to_string(),
+            seq,
+            ttl,
+            event,
+        };
+        Ok(Some((message, header_len + 1 + event_len)))
+    }
+}
+
+#[derive(Default)]
+struct SeenEvents {
+    ids: HashSet<(String, u64)>,
+    order: VecDeque<(String, u64)>,
+}
+
+impl SeenEvents {
+    // returns false if the event was seen before
+    fn insert(&mut self, origin: &str, seq: u64) -> bool {
+        let id = (origin.to_string(), seq);
+        if !self.ids.insert(id.clone()) {
+            return false;
+        }
+        self.order.push_back(id);
+        if self.order.len() > SEEN_CAPACITY
+            && let Some(oldest) = self.order.pop_front()
+        {
+            self.ids.remove(&oldest);
+        }
+        true
+    }
+}
+
+enum RouterInput {
+    LinkUp(usize, mpsc::UnboundedSender<Arc<Vec<u8>>>),
+    LinkDown(usize),
+    FromPeer(usize, PeerMessage),
+}
+
+struct Router<N: Number> {
+    // differs between restarts, so peers do not mistake new events for old ones
+    origin: String,
+    next_seq: u64,
+    seen: SeenEvents,
+    links: HashMap<usize, mpsc::UnboundedSender<Arc<Vec<u8>>>>,
+    peer_count: Arc<AtomicUsize>,
+    state: State<N>,
+}
+
+impl<N: Number> Router<N> {
+    fn forward(&self, message: &PeerMessage, except: Option<usize>) {
+        let encoded = Arc::new(message.encode());
+        for (link, sender) in &self.links {
+            if Some(*link) != except {
+                let _ = sender.send(encoded.clone());
+            }
+        }
+    }
+
+    fn handle_local_event(&mut self, event: Event) {
+        let message = PeerMessage {
+            origin: self.origin.clone(),
+            seq: self.next_seq,
+            ttl: DEFAULT_TTL,
+            event,
+        };
+        self.next_seq += 1;
+        self.seen.insert(&message.origin, message.seq);
+        self.forward(&message, None);
+    }
+
+    fn handle_peer_message(&mut self, link: usize, mut message: PeerMessage) {
+        if !self.seen.insert(&message.origin, message.seq) {
+            return;
+        }
+        let _ = self.state.send_peer_event(message.event.clone());
+        if message.ttl > 1 {
+            message.ttl -= 1;
+            self.forward(&message, Some(link));
+        }
+    }
+
+    async fn run(
+        mut self,
+        mut inputs: mpsc::UnboundedReceiver<RouterInput>,
+        mut events: mpsc::UnboundedReceiver<(EventOrigin, Event)>,
+    ) {
+        loop {
+            tokio::select! {
+                Some(input) = inputs.recv() => match input {
+                    RouterInput::LinkUp(link, sender) => {
+                        self.links.insert(link, sender);
+                    }
+                    RouterInput::LinkDown(link) => {
+                        self.links.remove(&link);
+                    }
+                    RouterInput::FromPeer(link, message) => self.handle_peer_message(link, message),
+                },
+                Some((origin, event)) = events.recv() => {
+                    // peer events were already forwarded when they arrived
+                    if origin == EventOrigin::Local {
+                        self.handle_local_event(event);
+                    }
+                }
+                else => return,
+            }
+            self.peer_count.store(self.links.len(), Ordering::Relaxed);
+        }
+    }
+}
+
+async fn hello(socket: &mut TcpStream, node_id: &str, buf: &mut BytesMut) -> io::Result<String> {
+    socket
+        .write_all(format!("PEER {node_id}\n").as_bytes())
+        .await?;
+    loop {
+        if let Some(pos) = buf.iter().position(|c| *c == b'\n') {
+            let line = String::from_utf8_lossy(&buf[..pos]).into_owned();
+            buf.advance(pos + 1);
+            let peer_id = line
+                .strip_prefix("PEER ")
+                .ok_or_else(|| invalid_message("missing hello"))?;
+            if peer_id == node_id {
+                return Err(invalid_message("link to itself"));
+            }
+            return Ok(peer_id.to_string());
+        }
+        if buf.len() > MAX_HELLO_SIZE {
+            return Err(invalid_message("hello too large"));
+        }
+        if 0 == socket.read_buf(buf).await? {
+            return Err(io::Error::from(ErrorKind::ConnectionAborted));
+        }
+    }
+}
+
+#[derive(Clone)]
+struct LinkContext {
+    node_id: Arc<str>,
+    router: mpsc::UnboundedSender<RouterInput>,
+    next_link: Arc<AtomicUsize>,
+}
+
+impl LinkContext {
+    async fn exchange(
+        &self,
+        link: usize,
+        socket: &mut TcpStream,
+        mut buf: BytesMut,
+        mut outgoing: mpsc::UnboundedReceiver<Arc<Vec<u8>>>,
+    ) -> io::Result<()> {
+        loop {
+            while let Some((message, message_len)) = PeerMessage::decode(&buf)? {
+                buf.advance(message_len);
+                let _ = self.router.send(RouterInput::FromPeer(link, message));
+            }
+            tokio::select! {
+                n = socket.read_buf(&mut buf) => {
+                    if 0 == n? {
+                        return Err(io::Error::from(ErrorKind::ConnectionAborted));
+                    }
+                }
+                Some(message) = outgoing.recv() => socket.write_all(&message).await?,
+                _ = self.router.closed() => return Ok(()),
+            }
+        }
+    }
+
+    async fn run(&self, mut socket: TcpStream) -> io::Result<()> {
+        let mut buf = BytesMut::with_capacity(1024);
+        let peer_id = hello(&mut socket, &self.node_id, &mut buf).await?;
+        let link = self.next_link.fetch_add(1, Ordering::Relaxed);
+        let (sender, outgoing) = mpsc::unbounded_channel();
+        if self.router.send(RouterInput::LinkUp(link, sender)).is_err() {
+            return Ok(());
+        }
+        println!("peer link {link} to {peer_id} is up");
+        let result = self.exchange(link, &mut socket, buf, outgoing).await;
+        println!("peer link {link} to {peer_id} is down");
+        let _ = self.router.send(RouterInput::LinkDown(link));
+        result
+    }
+
+    async fn accept_links(self, listener: TcpListener) {
+        loop {
+            let Ok((socket, _)) = listener.accept().await else {
+                continue;
+            };
+            let context = self.clone();
+            tokio::spawn(async move { context.run(socket).await });
+        }
+    }
+
+    async fn maintain_link(
+        self,
+        address: SocketAddr,
+        min_backoff: Duration,
+        max_backoff: Duration,
+    ) {
+        let mut backoff = min_backoff;
+        while !self.router.is_closed() {
+            if let Ok(socket) = TcpStream::connect(address).await {
+                backoff = min_backoff;
+                if let Err(e) = self.run(socket).await {
+                    eprintln!("peer link to {address} failed; err = {e:?}");
+                }
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(max_backoff);
+        }
+    }
+}
+
+/// Keeps the peer links of a server running, they are shut down when this is
+/// dropped.
+pub struct Cluster {
+    tasks: Vec<JoinHandle<()>>,
+    peer_count: Arc<AtomicUsize>,
+}
+
+impl Cluster {
+    /// Number of peer links that are currently up.
+    pub fn peer_count(&self) -> usize {
+        self.peer_count.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Cluster {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+/// Connects the event feed of `state` with the other servers of the cluster.
+/// Events typed on the local console are sent to all peers, events from peers
+/// are delivered to the local clients and passed on to the other peers.
+pub fn spawn_cluster<N: Number>(config: ClusterConfig, state: State<N>) -> Cluster {
+    let boot_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    let peer_count = Arc::new(AtomicUsize::new(0));
+    let (router_sender, inputs) = mpsc::unbounded_channel();
+    let events = state.subscribe_events();
+    let router = Router {
+        origin: format!("{}/{boot_time}", config.node_id),
+        next_seq: 0,
+        seen: SeenEvents::default(),
+        links: HashMap::new(),
+        peer_count: peer_count.clone(),
+        state,
+    };
+    let context = LinkContext {
+        node_id: config.node_id.into(),
+        router: router_sender,
+        next_link: Arc::new(AtomicUsize::new(0)),
+    };
+
+    let mut tasks = vec![tokio::spawn(router.run(inputs, events))];
+    if let Some(listener) = config.listener {
+        tasks.push(tokio::spawn(context.clone().accept_links(listener)));
+    }
+    for address in config.peers {
+        tasks.push(tokio::spawn(context.clone().maintain_link(
+            address,
+            config.min_backoff,
+            config.max_backoff,
+        )));
+    }
+    Cluster { tasks, peer_count }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    use super::{
+        Cluster, ClusterConfig, MAX_MESSAGE_SIZE, MAX_PEER_HEADER_SIZE, PeerMessage, SeenEvents,
+        spawn_cluster,
+    };
+    use crate::event::{Event, EventOrigin};
+    use crate::state::State;
+
+    fn message(text: &str) -> Event {
+        Event::new("message", Some("console"), text).unwrap()
+    }
+
+    #[test]
+    fn peer_message_round_trip() {
+        let message = PeerMessage {
+            origin: "a/123".to_string(),
+            seq: 7,
+            ttl: 3,
+            event: message("line 1\nline 2"),
+        };
+        let mut encoded = message.encode();
+        assert!(encoded.starts_with(b"@ a/123 7 3\n\n! message console 13\n"));
+        let len = encoded.len();
+        for partial in 0..len {
+            assert_eq!(None, PeerMessage::decode(&encoded[..partial]).unwrap());
+        }
+        encoded.extend_from_slice(b"@ next");
+        assert_eq!(Some((message, len)), PeerMessage::decode(&encoded).unwrap());
+        assert!(PeerMessage::decode(b"# a 1 1\n").is_err());
+        assert!(PeerMessage::decode(b"@ a x 1\n").is_err());
+    }
+
+    #[test]
+    fn peer_message_size_is_capped() {
+        let endless_header = vec![b'@'; MAX_PEER_HEADER_SIZE + 1];
+        let error = PeerMessage::decode(&endless_header).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+
+        let mut endless_event = b"@ a 1 1\n\n! message ".to_vec();
+        endless_event.resize(MAX_MESSAGE_SIZE + 1, b'x');
+        let error = PeerMessage::decode(&endless_event).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+    }
+
+    #[test]
+    fn seen_events_forgets_oldest() {
+        let mut seen = SeenEvents::default();
+        assert!(seen.insert("a", 0));
+        assert!(!seen.insert("a", 0));
+        assert!(seen.insert("b", 0));
+        for seq in 1..super::SEEN_CAPACITY as u64 {
+            assert!(seen.insert("a", seq));
+        }
+        assert!(!seen.insert("b", 0));
+        assert!(seen.insert("a", 0));
+    }
+
+    #[test]
+    fn node_id_is_one_word() {
+        assert_eq!("a/1", ClusterConfig::new("a/1").unwrap().node_id());
+        for node_id in ["", "a b", "a\nb", "a\r", "\u{e4}"] {
+            let error = ClusterConfig::new(node_id).err().unwrap();
+            assert_eq!(ErrorKind::InvalidInput, error.kind());
+        }
+    }
+
+    struct Node {
+        state: State<usize>,
+        events: mpsc::UnboundedReceiver<(EventOrigin, Event)>,
+        cluster: Cluster,
+    }
+
+    async fn start_node(node_id: &str, listener: TcpListener, peers: Vec<SocketAddr>) -> Node {
+        let state = State::<usize>::default();
+        let events = state.subscribe_events();
+        let mut config = ClusterConfig::new(node_id).unwrap();
+        config.listener = Some(listener);
+        config.peers = peers;
+        config.min_backoff = Duration::from_millis(10);
+        config.max_backoff = Duration::from_millis(50);
+        let cluster = spawn_cluster(config, state.clone());
+        Node {
+            state,
+            events,
+            cluster,
+        }
+    }
+
+    async fn bind() -> (TcpListener, SocketAddr) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        (listener, address)
+    }
+
+    async fn wait_for_peers(node: &Node, count: usize) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while node.cluster.peer_count() < count {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    async fn next_event(node: &mut Node) -> (EventOrigin, Event) {
+        tokio::time::timeout(Duration::from_secs(5), node.events.recv())
+            .await
+            .unwrap()
+            .unwrap()
+    }
+
+    fn assert_no_more_events(node: &mut Node) {
+        assert!(node.events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn ring_delivers_every_event_once() {
+        let (listener_a, address_a) = bind().await;
+        let (listener_b, address_b) = bind().await;
+        let (listener_c, address_c) = bind().await;
+        // a -> b -> c -> a is a cycle, without ids events would go around forever
+        let mut nodes = [
+            start_node("a", listener_a, vec![address_b]).await,
+            start_node("b", listener_b, vec![address_c]).await,
+            start_node("c", listener_c, vec![address_a]).await,
+        ];
+        for node in &nodes {
+            wait_for_peers(node, 2).await;
+        }
+
+        for (i, text) in ["from a", "from b", "from c"].into_iter().enumerate() {
+            let _ = nodes[i].state.send_event(message(text));
+            for (j, node) in nodes.iter_mut().enumerate() {
+                let expected_origin = match i == j {
+                    true => EventOrigin::Local,
+                    false => EventOrigin::Peer,
+                };
+                assert_eq!((expected_origin, message(text)), next_event(node).await);
+            }
+        }
+        // give duplicates a chance to show up
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        for node in &mut nodes {
+            assert_no_more_events(node);
+        }
+    }
+
+    #[tokio::test]
+    async fn events_are_relayed_through_the_middle() {
+        let (listener_a, _) = bind().await;
+        let (listener_b, address_b) = bind().await;
+        let (listener_c, _) = bind().await;
+        let mut a = start_node("a", listener_a, vec![address_b]).await;
+        let b = start_node("b", listener_b, vec![]).await;
+        let mut c = start_node("c", listener_c, vec![address_b]).await;
+        wait_for_peers(&b, 2).await;
+
+        let _ = a.state.send_event(message("hello c"));
+        assert_eq!(
+            (EventOrigin::Peer, message("hello c")),
+            next_event(&mut c).await
+        );
+        let _ = c.state.send_event(message("hello a"));
+        assert_eq!(
+            (EventOrigin::Local, message("hello c")),
+            next_event(&mut a).await
+        );
+        assert_eq!(
+            (EventOrigin::Peer, message("hello a")),
+            next_event(&mut a).await
+        );
+    }
+
+    #[tokio::test]
+    async fn link_reconnects_with_backoff() {
+        // reserve an address for b, but start b only after a tried to connect
+        let (listener_b, address_b) = bind().await;
+        drop(listener_b);
+        let (listener_a, _) = bind().await;
+        let mut a = start_node("a", listener_a, vec![address_b]).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(0, a.cluster.peer_count());
+
+        let listener_b = TcpListener::bind(address_b).await.unwrap();
+        let b = start_node("b", listener_b, vec![]).await;
+        wait_for_peers(&a, 1).await;
+        let _ = b.state.send_event(message("first"));
+        assert_eq!(
+            (EventOrigin::Peer, message("first")),
+            next_event(&mut a).await
+        );
+
+        // restart b on the same address
+        drop(b);
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while a.cluster.peer_count() > 0 {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let listener_b = TcpListener::bind(address_b).await.unwrap();
+        let b = start_node("b", listener_b, vec![]).await;
+        wait_for_peers(&a, 1).await;
+        let _ = b.state.send_event(message("second"));
+        assert_eq!(
+            (EventOrigin::Peer, message("second")),
+            next_event(&mut a).await
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_link_to_itself() {
+        let (listener, address) = bind().await;
+        let node = start_node("a", listener, vec![address]).await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(0, node.cluster.peer_count());
+    }
+}
@@ -5,7 +5,7 @@ use tokio::signal;
 use mockall::mock;
 
 pub trait CtrlCWaiter {
-    async fn ctrl_c_pressed(&self);
+    fn ctrl_c_pressed(&self) -> impl std::future::Future<Output = ()> + Send;
 }
 
 #[cfg(not(test))]
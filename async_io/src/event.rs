@@ -0,0 +1,230 @@
+use std::io::{self, ErrorKind};
+
+// Event frames on the wire look like
+//
+//   \n! <kind> <sender> <length>\n<payload>\n
+//
+// Prompts start with '<' or '>', so the leading newline tells clients that an
+// event frame follows. The payload is length delimited and may contain any
+// bytes, a missing sender is written as '-'.
+const FRAME_START: &[u8] = b"\n! ";
+const NO_SENDER: &str = "-";
+/// Largest payload a decoded event frame may announce.
+pub const MAX_PAYLOAD_SIZE: usize = 1 << 20;
+
+/// Where an event entered this server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventOrigin {
+    /// typed on the operator console
+    Local,
+    /// received over a peer link from another server
+    Peer,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Event {
+    kind: String,
+    sender: Option<String>,
+    payload: Vec<u8>,
+}
+
+impl Default for Event {
+    fn default() -> Self {
+        Event {
+            kind: "message".to_string(),
+            sender: None,
+            payload: Vec::new(),
+        }
+    }
+}
+
+// kind and sender are written as space separated words into the frame header
+pub(crate) fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|c| c.is_ascii_graphic())
+}
+
+fn invalid_frame(reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid event frame: {reason}"),
+    )
+}
+
+impl Event {
+    pub fn new(kind: &str, sender: Option<&str>, payload: impl Into<Vec<u8>>) -> io::Result<Event> {
+        if !is_token(kind) {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "invalid event kind",
+            ));
+        }
+        if let Some(sender) = sender
+            && (!is_token(sender) || sender == NO_SENDER)
+        {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "invalid event sender",
+            ));
+        }
+        Ok(Event {
+            kind: kind.to_string(),
+            sender: sender.map(str::to_string),
+            payload: payload.into(),
+        })
+    }
+
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+
+    pub fn sender(&self) -> Option<&str> {
+        self.sender.as_deref()
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.payload
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let header = format!(
+            "{} {} {}\n",
+            self.kind,
+            self.sender.as_deref().unwrap_or(NO_SENDER),
+            self.payload.len()
+        );
+        let mut frame =
+            Vec::with_capacity(FRAME_START.len() + header.len() + self.payload.len() + 1);
+        frame.extend_from_slice(FRAME_START);
+        frame.extend_from_slice(header.as_bytes());
+        frame.extend_from_slice(&self.payload);
+        frame.push(b'\n');
+        frame
+    }
+
+    /// Decodes the event frame at the start of `buf`. Returns the event and the
+    /// number of bytes it occupied, or `None` if more bytes are needed.
+    pub fn decode(buf: &[u8]) -> io::Result<Option<(Event, usize)>> {
+        let n = buf.len().min(FRAME_START.len());
+        if buf[..n] != FRAME_START[..n] {
+            return Err(invalid_frame("missing frame start"));
+        }
+        let Some(header_len) = buf[n..].iter().position(|c| *c == b'\n') else {
+            return Ok(None);
+        };
+        let header = std::str::from_utf8(&buf[FRAME_START.len()..n + header_len])
+            .map_err(|_| invalid_frame("header is not UTF-8"))?;
+        let [kind, sender, length] = header.split(' ').collect::<Vec<_>>()[..] else {
+            return Err(invalid_frame("header needs kind, sender and length"));
+        };
+        let length: usize = length
+            .parse()
+            .map_err(|_| invalid_frame("invalid payload length"))?;
+        if length > MAX_PAYLOAD_SIZE {
+            return Err(invalid_frame("payload is too large"));
+        }
+        let payload_start = n + header_len + 1;
+        let frame_len = payload_start
+            .checked_add(length)
+            .and_then(|len| len.checked_add(1))
+            .ok_or_else(|| invalid_frame("payload length overflows"))?;
+        if buf.len() < frame_len {
+            return Ok(None);
+        }
+        if buf[frame_len - 1] != b'\n' {
+            return Err(invalid_frame("payload is not terminated"));
+        }
+        let sender = (sender != NO_SENDER).then_some(sender);
+        let event = Event::new(kind, sender, &buf[payload_start..frame_len - 1])
+            .map_err(|e| invalid_frame(&e.to_string()))?;
+        Ok(Some((event, frame_len)))
+    }
+}
+
+/// Tells apart event frames from prompt frames by their first byte.
+pub fn starts_event_frame(buf: &[u8]) -> bool {
+    buf.first() == FRAME_START.first()
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+
+    use super::{Event, MAX_PAYLOAD_SIZE, starts_event_frame};
+
+    #[test]
+    fn encode_with_sender() {
+        let event = Event::new("message", Some("console"), "blub").unwrap();
+        assert_eq!(b"\n! message console 4\nblub\n".to_vec(), event.encode());
+    }
+
+    #[test]
+    fn encode_without_sender() {
+        let event = Event::new("alert", None, "").unwrap();
+        assert_eq!(b"\n! alert - 0\n\n".to_vec(), event.encode());
+    }
+
+    #[test]
+    fn round_trip_multi_line_and_binary_payload() {
+        let payloads: [&[u8]; 3] = [
+            b"line 1\nline 2\n",
+            b"\n! message fake 3\nabc\n< x = ",
+            &[0, 255, 10, 13, 0],
+        ];
+        for payload in payloads {
+            let event = Event::new("message", Some("console"), payload).unwrap();
+            let mut frame = event.encode();
+            let frame_len = frame.len();
+            frame.extend_from_slice(b"< y = ");
+            assert_eq!(Some((event, frame_len)), Event::decode(&frame).unwrap());
+        }
+    }
+
+    #[test]
+    fn decode_needs_whole_frame() {
+        let frame = Event::new("message", None, "blub").unwrap().encode();
+        for len in 0..frame.len() {
+            assert_eq!(None, Event::decode(&frame[..len]).unwrap());
+        }
+    }
+
+    #[test]
+    fn decode_rejects_malformed_frames() {
+        for frame in [
+            &b"< x = "[..],
+            b"\n? message - 1\na\n",
+            b"\n! message 1\na\n",
+            b"\n! message - x\na\n",
+            b"\n! message - 1\nab",
+            b"\n!  - 1\na\n",
+        ] {
+            let error = Event::decode(frame).unwrap_err();
+            assert_eq!(ErrorKind::InvalidData, error.kind());
+        }
+    }
+
+    #[test]
+    fn decode_rejects_oversized_payloads() {
+        let overflowing = Event::decode(b"\n! a b 18446744073709551615\nxx\n").unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, overflowing.kind());
+        let header = format!("\n! a b {}\n", MAX_PAYLOAD_SIZE + 1);
+        let too_large = Event::decode(header.as_bytes()).unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, too_large.kind());
+    }
+
+    #[test]
+    fn new_rejects_invalid_tokens() {
+        assert!(Event::new("", None, "").is_err());
+        assert!(Event::new("two words", None, "").is_err());
+        assert!(Event::new("message", Some("-"), "").is_err());
+        assert!(Event::new("message", Some("line\nbreak"), "").is_err());
+    }
+
+    #[test]
+    fn event_frames_differ_from_prompts() {
+        let frame = Event::default().encode();
+        assert!(starts_event_frame(&frame));
+        assert!(!starts_event_frame(b"< x = "));
+        assert!(!starts_event_frame(b"> z = 3\n"));
+        assert!(!starts_event_frame(b""));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod async_adder;
+pub mod big_int;
+pub mod cluster;
+pub mod ctrl_c_waiter;
+pub mod event;
+pub mod loadgen;
+pub mod state;
+pub mod stdio;
+pub mod websocket;
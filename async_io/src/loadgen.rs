@@ -0,0 +1,403 @@
+use std::fmt::Display;
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Notify;
+
+use crate::async_adder::{ServerOptions, main2};
+use crate::big_int::BigInt;
+use crate::ctrl_c_waiter::CtrlCWaiter;
+use crate::event::{Event, starts_event_frame};
+use crate::state::Number;
+use crate::stdio::Stdio;
+
+pub struct LoadConfig {
+    pub connections: usize,
+    pub rounds: usize,
+    pub think_time: Duration,
+    /// inject a broadcast event this often, `None` disables events
+    pub event_interval: Option<Duration>,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        Self {
+            connections: 10,
+            rounds: 100,
+            think_time: Duration::ZERO,
+            event_interval: Some(Duration::from_millis(10)),
+        }
+    }
+}
+
+/// Outcome of a load run. All connections share the same x and y on the
+/// server, so the values of z are only checked to be numbers, not to be the
+/// sum of what this connection sent.
+#[derive(Default)]
+pub struct LoadReport {
+    pub connections: usize,
+    pub rounds: usize,
+    pub errors: usize,
+    pub events_sent: usize,
+    pub events_received: usize,
+    pub elapsed: Duration,
+    pub latencies: Vec<Duration>,
+}
+
+impl LoadReport {
+    pub fn throughput(&self) -> f64 {
+        self.rounds as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Nearest-rank percentile of the round latencies, `p` is in `0.0..=1.0`.
+    pub fn percentile(&self, p: f64) -> Option<Duration> {
+        let mut sorted = self.latencies.clone();
+        sorted.sort();
+        let rank = (p * sorted.len() as f64).ceil() as usize;
+        sorted.get(rank.saturating_sub(1)).copied()
+    }
+}
+
+impl Display for LoadReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |p| {
+            self.percentile(p)
+                .map(|d| d.as_secs_f64() * 1000.0)
+                .unwrap_or(0.0)
+        };
+        writeln!(f, "connections:     {}", self.connections)?;
+        writeln!(
+            f,
+            "rounds:          {} in {:.3}s ({:.1} rounds/s)",
+            self.rounds,
+            self.elapsed.as_secs_f64(),
+            self.throughput()
+        )?;
+        writeln!(f, "latency p50:     {:.3}ms", ms(0.5))?;
+        writeln!(f, "latency p99:     {:.3}ms", ms(0.99))?;
+        writeln!(f, "errors:          {}", self.errors)?;
+        write!(
+            f,
+            "events:          {} injected, {} received",
+            self.events_sent, self.events_received
+        )
+    }
+}
+
+struct SessionResult {
+    latencies: Vec<Duration>,
+    events_received: usize,
+    error: Option<io::Error>,
+}
+
+struct Session {
+    socket: TcpStream,
+    buf: BytesMut,
+    events_received: usize,
+}
+
+impl Session {
+    // returns true if an incomplete event frame is left in the buffer
+    fn skip_events(&mut self) -> Result<bool, io::Error> {
+        while starts_event_frame(&self.buf) {
+            match Event::decode(&self.buf)? {
+                Some((_, frame_len)) => {
+                    self.buf.advance(frame_len);
+                    self.events_received += 1;
+                }
+                None => return Ok(true),
+            }
+        }
+        Ok(false)
+    }
+
+    async fn read_more(&mut self) -> Result<(), io::Error> {
+        if 0 == self.socket.read_buf(&mut self.buf).await? {
+            return Err(io::Error::from(ErrorKind::UnexpectedEof));
+        }
+        Ok(())
+    }
+
+    // event frames are only sent while the server waits for input, so they
+    // can show up in front of every prompt
+    async fn expect(&mut self, prompt: &str) -> Result<(), io::Error> {
+        let prompt = prompt.as_bytes();
+        loop {
+            let incomplete_event = self.skip_events()?;
+            if self.buf.starts_with(prompt) {
+                self.buf.advance(prompt.len());
+                return Ok(());
+            }
+            let n = self.buf.len().min(prompt.len());
+            if !incomplete_event && self.buf[..n] != prompt[..n] {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    format!("unexpected output {:?}", String::from_utf8_lossy(&self.buf)),
+                ));
+            }
+            self.read_more().await?;
+        }
+    }
+
+    async fn read_z(&mut self) -> Result<(), io::Error> {
+        self.expect("> z = ").await?;
+        loop {
+            if let Some(pos) = self.buf.iter().position(|c| *c == b'\n') {
+                let z = String::from_utf8_lossy(&self.buf[..pos]).parse::<BigInt>();
+                self.buf.advance(pos + 1);
+                return z
+                    .map(|_| ())
+                    .map_err(|e| io::Error::new(ErrorKind::InvalidData, e));
+            }
+            self.read_more().await?;
+        }
+    }
+
+    async fn send(&mut self, value: usize) -> Result<(), io::Error> {
+        self.socket.write_all(format!("{value}\n").as_bytes()).await
+    }
+
+    async fn run(
+        &mut self,
+        id: usize,
+        config: &LoadConfig,
+        latencies: &mut Vec<Duration>,
+    ) -> Result<(), io::Error> {
+        for round in 0..config.rounds {
+            self.expect("< x = ").await?;
+            if !config.think_time.is_zero() {
+                tokio::time::sleep(config.think_time).await;
+            }
+            let start = Instant::now();
+            self.send(round).await?;
+            self.expect("< y = ").await?;
+            self.send(id).await?;
+            self.read_z().await?;
+            latencies.push(start.elapsed());
+        }
+        Ok(())
+    }
+}
+
+async fn run_session(address: SocketAddr, id: usize, config: Arc<LoadConfig>) -> SessionResult {
+    let mut latencies = Vec::with_capacity(config.rounds);
+    let mut session = match TcpStream::connect(address).await {
+        Ok(socket) => Session {
+            socket,
+            buf: BytesMut::with_capacity(64),
+            events_received: 0,
+        },
+        Err(e) => {
+            return SessionResult {
+                latencies,
+                events_received: 0,
+                error: Some(e),
+            };
+        }
+    };
+    let error = session.run(id, &config, &mut latencies).await.err();
+    SessionResult {
+        latencies,
+        events_received: session.events_received,
+        error,
+    }
+}
+
+/// Runs the configured load against an already running server. Events are
+/// handed to `events` as if they were typed on the operator console.
+pub async fn run_load(
+    address: SocketAddr,
+    config: LoadConfig,
+    events: mpsc::Sender<String>,
+) -> LoadReport {
+    let config = Arc::new(config);
+    let events_sent = Arc::new(AtomicUsize::new(0));
+
+    let injector = config.event_interval.map(|interval| {
+        let events_sent = events_sent.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let n = events_sent.fetch_add(1, Ordering::Relaxed);
+                if events.send(format!("loadgen event {n}")).is_err() {
+                    return;
+                }
+            }
+        })
+    });
+
+    let start = Instant::now();
+    let sessions: Vec<_> = (0..config.connections)
+        .map(|id| tokio::spawn(run_session(address, id, config.clone())))
+        .collect();
+
+    let mut report = LoadReport {
+        connections: config.connections,
+        ..Default::default()
+    };
+    for session in sessions {
+        match session.await {
+            Ok(result) => {
+                report.rounds += result.latencies.len();
+                report.latencies.extend(result.latencies);
+                report.events_received += result.events_received;
+                if let Some(e) = result.error {
+                    eprintln!("session failure; err = {e:?}");
+                    report.errors += 1;
+                }
+            }
+            Err(_) => report.errors += 1,
+        }
+    }
+    report.elapsed = start.elapsed();
+
+    if let Some(injector) = injector {
+        injector.abort();
+    }
+    report.events_sent = events_sent.load(Ordering::Relaxed);
+    report
+}
+
+// replaces stdin of the operator console, every received string is one event
+struct EventFeed {
+    events: mpsc::Receiver<String>,
+}
+
+impl Stdio for EventFeed {
+    fn print(&self, _line: &str) -> io::Result<usize> {
+        Ok(0)
+    }
+
+    fn flush(&self) -> io::Result<usize> {
+        Ok(0)
+    }
+
+    fn read_line(&self, buffer: &mut String) -> io::Result<usize> {
+        let event = self
+            .events
+            .recv()
+            .map_err(|_| io::Error::from(ErrorKind::BrokenPipe))?;
+        buffer.push_str(&event);
+        Ok(event.len())
+    }
+}
+
+#[derive(Default)]
+struct LoadFinished {
+    notify: Notify,
+}
+
+impl CtrlCWaiter for LoadFinished {
+    async fn ctrl_c_pressed(&self) {
+        self.notify.notified().await;
+    }
+}
+
+/// Spawns a server on a random loopback port, runs the load against it and
+/// shuts the server down again.
+pub async fn run_with_local_server<N: Number>(
+    config: LoadConfig,
+) -> Result<LoadReport, Box<dyn std::error::Error>> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+    let (sender, receiver) = mpsc::channel();
+    let finished = LoadFinished::default();
+
+    let (server, report) = tokio::join!(
+        main2::<N, _>(
+            listener,
+            ServerOptions::default(),
+            &finished,
+            Box::new(EventFeed { events: receiver })
+        ),
+        async {
+            let report = run_load(address, config, sender).await;
+            finished.notify.notify_one();
+            report
+        }
+    );
+    server?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{sync::mpsc, time::Duration};
+
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+
+    use super::{LoadConfig, LoadReport, run_load, run_with_local_server};
+    use crate::big_int::BigInt;
+
+    #[test]
+    fn percentiles() {
+        let report = LoadReport {
+            latencies: (1..=100).rev().map(Duration::from_millis).collect(),
+            ..Default::default()
+        };
+        assert_eq!(Some(Duration::from_millis(50)), report.percentile(0.5));
+        assert_eq!(Some(Duration::from_millis(99)), report.percentile(0.99));
+        assert_eq!(Some(Duration::from_millis(100)), report.percentile(1.0));
+        assert_eq!(None, LoadReport::default().percentile(0.5));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_all_rounds_against_local_server() {
+        let config = LoadConfig {
+            connections: 4,
+            rounds: 5,
+            think_time: Duration::from_millis(2),
+            event_interval: Some(Duration::from_millis(1)),
+        };
+        let report = run_with_local_server::<usize>(config).await.unwrap();
+        assert_eq!(0, report.errors);
+        assert_eq!(20, report.rounds);
+        assert_eq!(20, report.latencies.len());
+        assert!(report.events_sent > 0);
+        assert!(report.percentile(0.5) <= report.percentile(0.99));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_against_big_int_server() {
+        let config = LoadConfig {
+            connections: 2,
+            rounds: 3,
+            event_interval: None,
+            ..Default::default()
+        };
+        let report = run_with_local_server::<BigInt>(config).await.unwrap();
+        assert_eq!(0, report.errors);
+        assert_eq!(6, report.rounds);
+        assert_eq!(0, report.events_sent);
+    }
+
+    #[tokio::test]
+    async fn counts_protocol_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                socket.write_all(b"< x = ").await.unwrap();
+                let _ = socket.write_all(b"no y prompt\n").await;
+            }
+        });
+        let config = LoadConfig {
+            connections: 3,
+            rounds: 2,
+            event_interval: None,
+            ..Default::default()
+        };
+        let (sender, _receiver) = mpsc::channel();
+        let report = run_load(address, config, sender).await;
+        assert_eq!(3, report.errors);
+        assert_eq!(0, report.rounds);
+    }
+}
@@ -1,16 +1,83 @@
-mod async_adder;
-mod ctrl_c_waiter;
-mod state;
-mod stdio;
+use std::error::Error;
+
+use async_io::async_adder::{self, ServerOptions};
+use async_io::cluster::ClusterConfig;
+use async_io::{big_int, ctrl_c_waiter, stdio};
+
+const USAGE: &str =
+    "usage: async_io [--big-int] [--websocket ADDR] [--node-id ID] [--peer-listen ADDR] [--peer ADDR]...
+
+  --big-int           x, y and z are not limited to the range of usize
+  --websocket ADDR    serve the adder to browsers over WebSocket on ADDR
+  --node-id ID        name of this server in the cluster (default adder)
+  --peer-listen ADDR  accept peer links from other servers on ADDR
+  --peer ADDR         keep a peer link to the server at ADDR, may be repeated";
+
+struct Args {
+    big_int: bool,
+    websocket: Option<String>,
+    node_id: Option<String>,
+    peer_listen: Option<String>,
+    peers: Vec<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut parsed = Args {
+        big_int: false,
+        websocket: None,
+        node_id: None,
+        peer_listen: None,
+        peers: Vec::new(),
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--big-int" {
+            parsed.big_int = true;
+            continue;
+        }
+        let value = args.next().ok_or(format!("missing value for {arg}"))?;
+        match arg.as_str() {
+            "--websocket" => parsed.websocket = Some(value),
+            "--node-id" => parsed.node_id = Some(value),
+            "--peer-listen" => parsed.peer_listen = Some(value),
+            "--peer" => parsed.peers.push(value),
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+    Ok(parsed)
+}
+
+async fn cluster_config(args: &Args) -> Result<Option<ClusterConfig>, Box<dyn Error>> {
+    if args.node_id.is_none() && args.peer_listen.is_none() && args.peers.is_empty() {
+        return Ok(None);
+    }
+    let mut config = ClusterConfig::new(args.node_id.as_deref().unwrap_or("adder"))?;
+    if let Some(address) = &args.peer_listen {
+        config.listener = Some(tokio::net::TcpListener::bind(address).await?);
+    }
+    for peer in &args.peers {
+        config.peers.push(peer.parse()?);
+    }
+    Ok(Some(config))
+}
 
-#[cfg(not(test))]
 #[tokio::main(flavor = "current_thread")]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
+async fn main() -> Result<(), Box<dyn Error>> {
+    let args = parse_args().map_err(|e| format!("{e}\n{USAGE}"))?;
     let listener = tokio::net::TcpListener::bind("127.0.0.1:8080").await?;
-    async_adder::main2(
-        listener,
-        &ctrl_c_waiter::CtrlCWaiterImpl::default(),
-        Box::new(stdio::StdioImpl::default()),
-    )
-    .await
+    let websocket_listener = match &args.websocket {
+        Some(address) => Some(tokio::net::TcpListener::bind(address).await?),
+        None => None,
+    };
+    let options = ServerOptions {
+        websocket_listener,
+        cluster: cluster_config(&args).await?,
+    };
+    let ctrl_c_waiter = ctrl_c_waiter::CtrlCWaiterImpl::default();
+    let stdio = Box::new(stdio::StdioImpl::default());
+    if args.big_int {
+        async_adder::main2::<big_int::BigInt, _>(listener, options, &ctrl_c_waiter, stdio).await
+    } else {
+        async_adder::main2::<usize, _>(listener, options, &ctrl_c_waiter, stdio).await
+    }
 }
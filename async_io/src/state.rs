@@ -1,90 +1,147 @@
+use std::fmt::Display;
+use std::ops::Add;
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
 
+use tokio::sync::mpsc;
 use tokio::sync::watch as Channel_type;
 
-struct LeSharedState {
+use crate::event::{Event, EventOrigin};
+
+/// Values the server can add up. `usize` is the default, `BigInt` is used in
+/// big-integer mode.
+pub trait Number:
+    Add<Output = Self>
+    + FromStr<Err: std::error::Error + Send + Sync + 'static>
+    + Display
+    + Default
+    + Clone
+    + Send
+    + 'static
+{
+}
+
+impl<N> Number for N where
+    N: Add<Output = N>
+        + FromStr<Err: std::error::Error + Send + Sync + 'static>
+        + Display
+        + Default
+        + Clone
+        + Send
+        + 'static
+{
+}
+
+struct LeSharedState<N: Number> {
     counter: usize,
-    x: usize,
-    y: usize,
-    sender: Channel_type::Sender<String>,
+    x: N,
+    y: N,
+    sender: Channel_type::Sender<Event>,
+    // unlike the watch channel these see every single event
+    event_subscribers: Vec<mpsc::UnboundedSender<(EventOrigin, Event)>>,
 }
 
-impl Default for LeSharedState {
+impl<N: Number> Default for LeSharedState<N> {
     fn default() -> Self {
-        let sender = Channel_type::Sender::<String>::new("".to_string());
+        let sender = Channel_type::Sender::<Event>::new(Event::default());
         Self {
             counter: Default::default(),
             x: Default::default(),
             y: Default::default(),
             sender,
+            event_subscribers: Default::default(),
         }
     }
 }
 
-fn exchange(current: &mut usize, new: &usize) -> usize {
-    let old_current = *current;
-    *current = *new;
-    old_current
+fn exchange<N>(current: &mut N, new: N) -> N {
+    std::mem::replace(current, new)
 }
 
-impl LeSharedState {
+impl<N: Number> LeSharedState<N> {
     pub fn inc_counter(&mut self) -> usize {
         self.counter += 1;
         self.counter
     }
 
-    pub fn set_x(&mut self, x: usize) -> usize {
-        exchange(&mut self.x, &x)
+    pub fn set_x(&mut self, x: N) -> N {
+        exchange(&mut self.x, x)
     }
 
-    pub fn set_y(&mut self, y: usize) -> usize {
-        exchange(&mut self.y, &y)
+    pub fn set_y(&mut self, y: N) -> N {
+        exchange(&mut self.y, y)
     }
 
-    pub fn get_z(&self) -> usize {
-        self.x + self.y
+    pub fn get_z(&self) -> N {
+        self.x.clone() + self.y.clone()
     }
 
-    pub fn send_event(&self, event: &str) -> Result<(), Channel_type::error::SendError<String>> {
-        self.sender.send(event.to_string())
+    pub fn send_event(
+        &mut self,
+        origin: EventOrigin,
+        event: Event,
+    ) -> Result<(), Channel_type::error::SendError<Event>> {
+        self.event_subscribers
+            .retain(|subscriber| subscriber.send((origin, event.clone())).is_ok());
+        self.sender.send(event)
     }
 
-    pub fn get_event_update_receiver(&self) -> Channel_type::Receiver<String> {
+    pub fn get_event_update_receiver(&self) -> Channel_type::Receiver<Event> {
         self.sender.subscribe()
     }
+
+    pub fn subscribe_events(&mut self) -> mpsc::UnboundedReceiver<(EventOrigin, Event)> {
+        let (subscriber, receiver) = mpsc::unbounded_channel();
+        self.event_subscribers.push(subscriber);
+        receiver
+    }
 }
 
 #[derive(Clone, Default)]
-pub struct State {
-    state: Arc<Mutex<LeSharedState>>,
+pub struct State<N: Number> {
+    state: Arc<Mutex<LeSharedState<N>>>,
 }
 
-impl State {
+impl<N: Number> State<N> {
     pub fn inc_counter(&mut self) -> usize {
         l(&self.state).inc_counter()
     }
 
-    pub fn set_x(&mut self, x: usize) -> usize {
+    pub fn set_x(&mut self, x: N) -> N {
         l(&self.state).set_x(x)
     }
 
-    pub fn set_y(&mut self, y: usize) -> usize {
+    pub fn set_y(&mut self, y: N) -> N {
         l(&self.state).set_y(y)
     }
 
-    pub fn get_z(&self) -> usize {
+    pub fn get_z(&self) -> N {
         l(&self.state).get_z()
     }
 
-    pub fn send_event(&self, event: &str) -> Result<(), Channel_type::error::SendError<String>> {
-        l(&self.state).send_event(event)
+    pub fn send_event(&self, event: Event) -> Result<(), Channel_type::error::SendError<Event>> {
+        l(&self.state).send_event(EventOrigin::Local, event)
+    }
+
+    /// Delivers an event that was received from another server.
+    pub fn send_peer_event(
+        &self,
+        event: Event,
+    ) -> Result<(), Channel_type::error::SendError<Event>> {
+        l(&self.state).send_event(EventOrigin::Peer, event)
     }
 
-    pub fn get_event_update_receiver(&self) -> Channel_type::Receiver<String> {
+    pub fn get_event_update_receiver(&self) -> Channel_type::Receiver<Event> {
         l(&self.state).get_event_update_receiver()
     }
+
+    pub fn subscribe_events(&self) -> mpsc::UnboundedReceiver<(EventOrigin, Event)> {
+        l(&self.state).subscribe_events()
+    }
 }
 
-fn l(state: &Arc<Mutex<LeSharedState>>) -> std::sync::MutexGuard<'_, LeSharedState> {
+fn l<N: Number>(
+    state: &Arc<Mutex<LeSharedState<N>>>,
+) -> std::sync::MutexGuard<'_, LeSharedState<N>> {
     state.lock().unwrap()
 }
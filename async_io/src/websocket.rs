@@ -0,0 +1,801 @@
+use std::io::{self, ErrorKind};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Buf, BytesMut};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+use tokio::sync::mpsc;
+
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+const MAX_PAYLOAD_SIZE: usize = 64 * 1024;
+
+pub const OPCODE_CONTINUATION: u8 = 0x0;
+pub const OPCODE_TEXT: u8 = 0x1;
+pub const OPCODE_BINARY: u8 = 0x2;
+pub const OPCODE_CLOSE: u8 = 0x8;
+pub const OPCODE_PING: u8 = 0x9;
+pub const OPCODE_PONG: u8 = 0xA;
+
+const CLOSE_NORMAL: u16 = 1000;
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+const CLOSE_TOO_BIG: u16 = 1009;
+
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+
+    let mut digest = [0; 20];
+    for (chunk, v) in digest.chunks_mut(4).zip(h) {
+        chunk.copy_from_slice(&v.to_be_bytes());
+    }
+    digest
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(bits >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Value of `Sec-WebSocket-Accept` for the `Sec-WebSocket-Key` of a client.
+pub fn accept_key(key: &str) -> String {
+    base64(&sha1(format!("{key}{ACCEPT_GUID}").as_bytes()))
+}
+
+fn bad_request(reason: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidData,
+        format!("bad upgrade request: {reason}"),
+    )
+}
+
+// returns the Sec-WebSocket-Key of a valid upgrade request
+fn parse_upgrade_request(request: &str) -> io::Result<String> {
+    let mut lines = request.split("\r\n");
+    let request_line = lines.next().unwrap_or_default();
+    let mut parts = request_line.split(' ');
+    if parts.next() != Some("GET") || parts.nth(1) != Some("HTTP/1.1") {
+        return Err(bad_request("expected GET with HTTP/1.1"));
+    }
+
+    let (mut upgrade, mut connection, mut version, mut key) = (false, false, false, None);
+    for line in lines.take_while(|line| !line.is_empty()) {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| bad_request("malformed header"))?;
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "upgrade" => upgrade = value.eq_ignore_ascii_case("websocket"),
+            "connection" => {
+                connection = value
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case("upgrade"))
+            }
+            "sec-websocket-version" => version = value == "13",
+            "sec-websocket-key" => key = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    match key {
+        Some(key) if upgrade && connection && version && !key.is_empty() => Ok(key),
+        _ => Err(bad_request("missing or wrong upgrade headers")),
+    }
+}
+
+/// Reads the HTTP upgrade request from `socket` and answers it. Bytes that
+/// follow the request are left in `buf` as they already belong to frames.
+pub async fn handshake<Socket>(socket: &mut Socket, buf: &mut BytesMut) -> io::Result<()>
+where
+    Socket: AsyncRead + AsyncWrite + Unpin,
+{
+    let request_len = loop {
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+        if buf.len() > MAX_REQUEST_SIZE {
+            return Err(bad_request("request too large"));
+        }
+        if 0 == socket.read_buf(buf).await? {
+            return Err(io::Error::from(ErrorKind::ConnectionAborted));
+        }
+    };
+    let request = String::from_utf8_lossy(&buf[..request_len]).into_owned();
+    buf.advance(request_len);
+
+    match parse_upgrade_request(&request) {
+        Ok(key) => {
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\n\
+                 Upgrade: websocket\r\n\
+                 Connection: Upgrade\r\n\
+                 Sec-WebSocket-Accept: {}\r\n\r\n",
+                accept_key(&key)
+            );
+            socket.write_all(response.as_bytes()).await?;
+            socket.flush().await
+        }
+        Err(e) => {
+            socket
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+                .await?;
+            socket.flush().await?;
+            Err(e)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: u8,
+    pub masked: bool,
+    pub payload: Vec<u8>,
+}
+
+/// Encodes a single frame. Clients have to pass a `mask`, servers must not.
+pub fn encode_frame(opcode: u8, payload: &[u8], mask: Option<[u8; 4]>) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(payload.len() + 14);
+    frame.push(0x80 | opcode);
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 };
+    match payload.len() {
+        len @ 0..=125 => frame.push(mask_bit | len as u8),
+        len @ 126..=0xFFFF => {
+            frame.push(mask_bit | 126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(mask_bit | 127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    match mask {
+        Some(mask) => {
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().zip(mask.iter().cycle()).map(|(b, m)| b ^ m));
+        }
+        None => frame.extend_from_slice(payload),
+    }
+    frame
+}
+
+/// Decodes the frame at the start of `buf` and removes the mask of its
+/// payload. Returns the frame and its size, or `None` if more bytes are
+/// needed.
+pub fn decode_frame(buf: &[u8]) -> io::Result<Option<(Frame, usize)>> {
+    if buf.len() < 2 {
+        return Ok(None);
+    }
+    if buf[0] & 0x70 != 0 {
+        return Err(io::Error::new(ErrorKind::InvalidData, "reserved bits set"));
+    }
+    let masked = buf[1] & 0x80 != 0;
+    let (payload_len, mut pos) = match buf[1] & 0x7F {
+        126 if buf.len() >= 4 => (u16::from_be_bytes([buf[2], buf[3]]) as u64, 4),
+        127 if buf.len() >= 10 => (u64::from_be_bytes(buf[2..10].try_into().unwrap()), 10),
+        126 | 127 => return Ok(None),
+        len => (len as u64, 2),
+    };
+    let fin = buf[0] & 0x80 != 0;
+    let opcode = buf[0] & 0x0F;
+    // control frames must fit a single frame with the short length form
+    if opcode & 0x8 != 0 && (!fin || payload_len > 125) {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "control frame is fragmented or too long",
+        ));
+    }
+    if payload_len > MAX_PAYLOAD_SIZE as u64 {
+        return Err(io::Error::new(ErrorKind::OutOfMemory, "frame too large"));
+    }
+    let payload_len = payload_len as usize;
+    let mask = if masked {
+        if buf.len() < pos + 4 {
+            return Ok(None);
+        }
+        pos += 4;
+        Some([buf[pos - 4], buf[pos - 3], buf[pos - 2], buf[pos - 1]])
+    } else {
+        None
+    };
+    if buf.len() < pos + payload_len {
+        return Ok(None);
+    }
+    let payload = &buf[pos..pos + payload_len];
+    let payload = match mask {
+        Some(mask) => payload
+            .iter()
+            .zip(mask.iter().cycle())
+            .map(|(b, m)| b ^ m)
+            .collect(),
+        None => payload.to_vec(),
+    };
+    let frame = Frame {
+        fin,
+        opcode,
+        masked,
+        payload,
+    };
+    Ok(Some((frame, pos + payload_len)))
+}
+
+async fn send_close<Writer>(socket: &mut Writer, code: u16) -> io::Result<()>
+where
+    Writer: AsyncWrite + Unpin,
+{
+    socket
+        .write_all(&encode_frame(OPCODE_CLOSE, &code.to_be_bytes(), None))
+        .await?;
+    socket.flush().await
+}
+
+/// Byte stream end of a WebSocket session for code like `Connection` that
+/// expects a socket. Everything written between two flushes becomes one
+/// message, reads return the payload of the received data frames.
+pub struct MessageStream {
+    incoming: mpsc::UnboundedReceiver<Vec<u8>>,
+    pending: BytesMut,
+    outgoing: mpsc::UnboundedSender<Vec<u8>>,
+    message: Vec<u8>,
+}
+
+/// The other end of a `MessageStream`, driven by `bridge`.
+pub struct MessagePeer {
+    incoming: mpsc::UnboundedSender<Vec<u8>>,
+    outgoing: mpsc::UnboundedReceiver<Vec<u8>>,
+}
+
+pub fn message_pipe() -> (MessageStream, MessagePeer) {
+    let (incoming_sender, incoming_receiver) = mpsc::unbounded_channel();
+    let (outgoing_sender, outgoing_receiver) = mpsc::unbounded_channel();
+    (
+        MessageStream {
+            incoming: incoming_receiver,
+            pending: BytesMut::new(),
+            outgoing: outgoing_sender,
+            message: Vec::new(),
+        },
+        MessagePeer {
+            incoming: incoming_sender,
+            outgoing: outgoing_receiver,
+        },
+    )
+}
+
+impl AsyncRead for MessageStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.pending.is_empty() {
+            match self.incoming.poll_recv(cx) {
+                Poll::Ready(Some(payload)) => self.pending.extend_from_slice(&payload),
+                // closed peer reads as end of file
+                Poll::Ready(None) => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
+            }
+        }
+        let n = self.pending.len().min(buf.remaining());
+        buf.put_slice(&self.pending[..n]);
+        self.pending.advance(n);
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for MessageStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.message.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.message.is_empty() {
+            return Poll::Ready(Ok(()));
+        }
+        let message = std::mem::take(&mut self.message);
+        Poll::Ready(
+            self.outgoing
+                .send(message)
+                .map_err(|_| io::Error::from(ErrorKind::BrokenPipe)),
+        )
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Carries the messages of `peer` over the WebSocket `socket`. Messages are
+/// sent as text if they are UTF-8 and as binary otherwise. The payload of
+/// incoming data frames is handed to `peer` as is. Returns when either side
+/// closes.
+pub async fn bridge<Socket>(
+    socket: Socket,
+    mut buf: BytesMut,
+    mut peer: MessagePeer,
+) -> io::Result<()>
+where
+    Socket: AsyncRead + AsyncWrite + Unpin,
+{
+    let (mut socket_reader, mut socket_writer) = tokio::io::split(socket);
+    // whether a data frame without FIN started a message that continues
+    let mut fragmented = false;
+
+    loop {
+        // frames may already be buffered, e.g. right after the handshake
+        while let Some((frame, frame_len)) = match decode_frame(&buf) {
+            Ok(frame) => frame,
+            Err(e) => {
+                let code = match e.kind() {
+                    ErrorKind::OutOfMemory => CLOSE_TOO_BIG,
+                    _ => CLOSE_PROTOCOL_ERROR,
+                };
+                send_close(&mut socket_writer, code).await?;
+                return Err(e);
+            }
+        } {
+            buf.advance(frame_len);
+            if !frame.masked {
+                send_close(&mut socket_writer, CLOSE_PROTOCOL_ERROR).await?;
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "unmasked client frame",
+                ));
+            }
+            match frame.opcode {
+                OPCODE_CONTINUATION | OPCODE_TEXT | OPCODE_BINARY => {
+                    if fragmented != (frame.opcode == OPCODE_CONTINUATION) {
+                        send_close(&mut socket_writer, CLOSE_PROTOCOL_ERROR).await?;
+                        return Err(io::Error::new(
+                            ErrorKind::InvalidData,
+                            "unexpected message fragment",
+                        ));
+                    }
+                    fragmented = !frame.fin;
+                    if peer.incoming.send(frame.payload).is_err() {
+                        return send_close(&mut socket_writer, CLOSE_NORMAL).await;
+                    }
+                }
+                OPCODE_PING => {
+                    socket_writer
+                        .write_all(&encode_frame(OPCODE_PONG, &frame.payload, None))
+                        .await?;
+                    socket_writer.flush().await?;
+                }
+                OPCODE_PONG => {}
+                OPCODE_CLOSE => return send_close(&mut socket_writer, CLOSE_NORMAL).await,
+                _ => {
+                    send_close(&mut socket_writer, CLOSE_PROTOCOL_ERROR).await?;
+                    return Err(io::Error::new(ErrorKind::InvalidData, "unknown opcode"));
+                }
+            }
+        }
+
+        tokio::select! {
+            n = socket_reader.read_buf(&mut buf) => {
+                if 0 == n? {
+                    return Err(io::Error::from(ErrorKind::ConnectionAborted));
+                }
+            }
+            message = peer.outgoing.recv() => {
+                let Some(message) = message else {
+                    return send_close(&mut socket_writer, CLOSE_NORMAL).await;
+                };
+                let opcode = match std::str::from_utf8(&message) {
+                    Ok(_) => OPCODE_TEXT,
+                    Err(_) => OPCODE_BINARY,
+                };
+                socket_writer.write_all(&encode_frame(opcode, &message, None)).await?;
+                socket_writer.flush().await?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test_client {
+    use std::io;
+
+    use bytes::{Buf, BytesMut};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::{Frame, accept_key, decode_frame, encode_frame};
+
+    pub const KEY: &str = "dGhlIHNhbXBsZSBub25jZQ==";
+
+    /// Minimal WebSocket client for tests, it masks every frame it sends.
+    pub struct TestClient {
+        pub socket: TcpStream,
+        pub buf: BytesMut,
+    }
+
+    impl TestClient {
+        pub async fn connect(address: std::net::SocketAddr) -> io::Result<TestClient> {
+            let mut socket = TcpStream::connect(address).await?;
+            socket
+                .write_all(
+                    format!(
+                        "GET /adder HTTP/1.1\r\n\
+                         Host: localhost\r\n\
+                         Upgrade: websocket\r\n\
+                         Connection: keep-alive, Upgrade\r\n\
+                         Sec-WebSocket-Key: {KEY}\r\n\
+                         Sec-WebSocket-Version: 13\r\n\r\n"
+                    )
+                    .as_bytes(),
+                )
+                .await?;
+            let mut client = TestClient {
+                socket,
+                buf: BytesMut::new(),
+            };
+            let response = loop {
+                if let Some(pos) = client.buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let response = String::from_utf8_lossy(&client.buf[..pos]).into_owned();
+                    client.buf.advance(pos + 4);
+                    break response;
+                }
+                if 0 == client.socket.read_buf(&mut client.buf).await? {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+                }
+            };
+            assert!(response.starts_with("HTTP/1.1 101 Switching Protocols\r\n"));
+            assert!(response.contains(&format!("Sec-WebSocket-Accept: {}", accept_key(KEY))));
+            Ok(client)
+        }
+
+        pub async fn send(&mut self, opcode: u8, payload: &[u8]) -> io::Result<()> {
+            self.socket
+                .write_all(&encode_frame(
+                    opcode,
+                    payload,
+                    Some([0x12, 0x34, 0x56, 0x78]),
+                ))
+                .await
+        }
+
+        pub async fn receive(&mut self) -> io::Result<Frame> {
+            loop {
+                if let Some((frame, frame_len)) = decode_frame(&self.buf)? {
+                    self.buf.advance(frame_len);
+                    return Ok(frame);
+                }
+                if 0 == self.socket.read_buf(&mut self.buf).await? {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    use super::test_client::TestClient;
+    use super::{
+        Frame, OPCODE_BINARY, OPCODE_CLOSE, OPCODE_CONTINUATION, OPCODE_PING, OPCODE_PONG,
+        OPCODE_TEXT, accept_key, base64, bridge, decode_frame, encode_frame, handshake,
+        message_pipe, sha1,
+    };
+
+    fn hex(digest: &[u8]) -> String {
+        digest.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    #[test]
+    fn sha1_test_vectors() {
+        assert_eq!("da39a3ee5e6b4b0d3255bfef95601890afd80709", hex(&sha1(b"")));
+        assert_eq!(
+            "a9993e364706816aba3e25717850c26c9cd0d89d",
+            hex(&sha1(b"abc"))
+        );
+        assert_eq!(
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1",
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            ))
+        );
+    }
+
+    #[test]
+    fn base64_padding() {
+        assert_eq!("", base64(b""));
+        assert_eq!("Zg==", base64(b"f"));
+        assert_eq!("Zm8=", base64(b"fo"));
+        assert_eq!("Zm9v", base64(b"foo"));
+        assert_eq!("Zm9vYg==", base64(b"foob"));
+    }
+
+    #[test]
+    fn accept_key_from_rfc() {
+        assert_eq!(
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=",
+            accept_key("dGhlIHNhbXBsZSBub25jZQ==")
+        );
+    }
+
+    #[test]
+    fn frame_round_trip() {
+        for len in [0, 5, 125, 126, 1000, 0xFFFF, 0x10000] {
+            let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+            for mask in [None, Some([1, 2, 3, 4])] {
+                let encoded = encode_frame(OPCODE_BINARY, &payload, mask);
+                let expected = Frame {
+                    fin: true,
+                    opcode: OPCODE_BINARY,
+                    masked: mask.is_some(),
+                    payload: payload.clone(),
+                };
+                assert_eq!(
+                    Some((expected, encoded.len())),
+                    decode_frame(&encoded).unwrap()
+                );
+                assert_eq!(None, decode_frame(&encoded[..encoded.len() - 1]).unwrap());
+            }
+        }
+    }
+
+    #[test]
+    fn masked_frame_bytes() {
+        // example from RFC 6455 section 5.7
+        assert_eq!(
+            vec![
+                0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58
+            ],
+            encode_frame(OPCODE_TEXT, b"Hello", Some([0x37, 0xfa, 0x21, 0x3d]))
+        );
+        assert_eq!(
+            vec![0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f],
+            encode_frame(OPCODE_TEXT, b"Hello", None)
+        );
+    }
+
+    #[test]
+    fn decode_rejects_oversized_and_reserved() {
+        let mut header = vec![0x82, 127];
+        header.extend_from_slice(&(1u64 << 40).to_be_bytes());
+        assert!(decode_frame(&header).is_err());
+        assert!(decode_frame(&[0xC1, 0x00]).is_err());
+    }
+
+    #[test]
+    fn decode_rejects_invalid_control_frames() {
+        let long_ping = encode_frame(OPCODE_PING, &[0; 126], None);
+        assert!(decode_frame(&long_ping).is_err());
+        let mut fragmented_close = encode_frame(OPCODE_CLOSE, b"", None);
+        fragmented_close[0] &= 0x7F;
+        assert!(decode_frame(&fragmented_close).is_err());
+        let ping = encode_frame(OPCODE_PING, &[0; 125], None);
+        assert!(decode_frame(&ping).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn handshake_rejects_plain_http() {
+        let (mut client, mut server) = tokio::io::duplex(1024);
+        client
+            .write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = BytesMut::new();
+        assert!(handshake(&mut server, &mut buf).await.is_err());
+        drop(server);
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    }
+
+    async fn spawn_echo_gateway() -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = BytesMut::new();
+            handshake(&mut socket, &mut buf).await.unwrap();
+            let (mut echo, peer) = message_pipe();
+            tokio::spawn(async move {
+                let mut data = [0; 100];
+                loop {
+                    let n = echo.read(&mut data).await.unwrap();
+                    if n == 0 {
+                        return;
+                    }
+                    echo.write_all(&data[..n]).await.unwrap();
+                    echo.flush().await.unwrap();
+                }
+            });
+            let _ = bridge(socket, buf, peer).await;
+        });
+        address
+    }
+
+    #[tokio::test]
+    async fn message_stream_sends_one_message_per_flush() {
+        let (mut stream, mut peer) = message_pipe();
+        stream.write_all(b"> z = 42\n").await.unwrap();
+        stream.flush().await.unwrap();
+        stream.write_all(b"< x").await.unwrap();
+        stream.write_all(b" = ").await.unwrap();
+        stream.flush().await.unwrap();
+        stream.flush().await.unwrap();
+        assert_eq!(Some(b"> z = 42\n".to_vec()), peer.outgoing.recv().await);
+        assert_eq!(Some(b"< x = ".to_vec()), peer.outgoing.recv().await);
+
+        peer.incoming.send(b"12".to_vec()).unwrap();
+        peer.incoming.send(b"3\n".to_vec()).unwrap();
+        drop(peer);
+        let mut received = String::new();
+        stream.read_to_string(&mut received).await.unwrap();
+        assert_eq!("123\n", received);
+        assert!(stream.flush().await.is_ok());
+        stream.write_all(b"lost").await.unwrap();
+        assert!(stream.flush().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn bridge_echoes_text_and_binary() {
+        let mut client = TestClient::connect(spawn_echo_gateway().await)
+            .await
+            .unwrap();
+        client.send(OPCODE_TEXT, b"hello").await.unwrap();
+        let frame = client.receive().await.unwrap();
+        assert_eq!((OPCODE_TEXT, false), (frame.opcode, frame.masked));
+        assert_eq!(b"hello".to_vec(), frame.payload);
+
+        client.send(OPCODE_BINARY, &[0xff, 0x00]).await.unwrap();
+        let frame = client.receive().await.unwrap();
+        assert_eq!(OPCODE_BINARY, frame.opcode);
+        assert_eq!(vec![0xff, 0x00], frame.payload);
+    }
+
+    #[tokio::test]
+    async fn bridge_answers_ping_and_close() {
+        let mut client = TestClient::connect(spawn_echo_gateway().await)
+            .await
+            .unwrap();
+        client.send(OPCODE_PING, b"are you there").await.unwrap();
+        let frame = client.receive().await.unwrap();
+        assert_eq!(OPCODE_PONG, frame.opcode);
+        assert_eq!(b"are you there".to_vec(), frame.payload);
+
+        client
+            .send(OPCODE_CLOSE, &1000u16.to_be_bytes())
+            .await
+            .unwrap();
+        let frame = client.receive().await.unwrap();
+        assert_eq!(OPCODE_CLOSE, frame.opcode);
+        assert_eq!(1000u16.to_be_bytes().to_vec(), frame.payload);
+    }
+
+    #[tokio::test]
+    async fn bridge_closes_on_unmasked_frame() {
+        let mut client = TestClient::connect(spawn_echo_gateway().await)
+            .await
+            .unwrap();
+        client
+            .socket
+            .write_all(&encode_frame(OPCODE_TEXT, b"hello", None))
+            .await
+            .unwrap();
+        let frame = client.receive().await.unwrap();
+        assert_eq!(OPCODE_CLOSE, frame.opcode);
+        assert_eq!(1002u16.to_be_bytes().to_vec(), frame.payload);
+    }
+
+    /// Sends a masked data frame with FIN clear, i.e. not the last fragment.
+    async fn send_fragment(client: &mut TestClient, opcode: u8, payload: &[u8]) {
+        let mut frame = encode_frame(opcode, payload, Some([1, 2, 3, 4]));
+        frame[0] &= 0x7F;
+        client.socket.write_all(&frame).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn bridge_joins_fragments() {
+        let mut client = TestClient::connect(spawn_echo_gateway().await)
+            .await
+            .unwrap();
+        send_fragment(&mut client, OPCODE_TEXT, b"hel").await;
+        client.send(OPCODE_CONTINUATION, b"lo").await.unwrap();
+        let mut received = Vec::new();
+        while received.len() < 5 {
+            received.extend(client.receive().await.unwrap().payload);
+        }
+        assert_eq!(b"hello".to_vec(), received);
+    }
+
+    #[tokio::test]
+    async fn bridge_closes_on_unexpected_fragment() {
+        let mut client = TestClient::connect(spawn_echo_gateway().await)
+            .await
+            .unwrap();
+        client.send(OPCODE_CONTINUATION, b"lost").await.unwrap();
+        let frame = client.receive().await.unwrap();
+        assert_eq!(OPCODE_CLOSE, frame.opcode);
+        assert_eq!(1002u16.to_be_bytes().to_vec(), frame.payload);
+
+        let mut client = TestClient::connect(spawn_echo_gateway().await)
+            .await
+            .unwrap();
+        send_fragment(&mut client, OPCODE_TEXT, b"hel").await;
+        client.send(OPCODE_TEXT, b"lo").await.unwrap();
+        loop {
+            let frame = client.receive().await.unwrap();
+            if frame.opcode == OPCODE_CLOSE {
+                assert_eq!(1002u16.to_be_bytes().to_vec(), frame.payload);
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn bridge_closes_on_long_ping() {
+        let mut client = TestClient::connect(spawn_echo_gateway().await)
+            .await
+            .unwrap();
+        client.send(OPCODE_PING, &[0; 126]).await.unwrap();
+        let frame = client.receive().await.unwrap();
+        assert_eq!(OPCODE_CLOSE, frame.opcode);
+        assert_eq!(1002u16.to_be_bytes().to_vec(), frame.payload);
+    }
+}
@@ -0,0 +1,16 @@
+use matrix::{Matrix, mmatrix};
+
+fn main() {
+    let mut m: Matrix<i32> = Matrix::new(4, 4);
+    m.set(1, 1, 3);
+    println!("{m}");
+
+    let m2 = mmatrix!(1,2,3;4,5,6;7,8,9);
+    println!("m2: {m2}");
+    for (i, row) in m2.iter_rows().enumerate() {
+        println!("row {i}: {row:?}");
+    }
+
+    let identity = Matrix::from_fn(3, 3, |row, col| i32::from(row == col));
+    println!("m2 * identity: {}", m2.clone() * identity);
+}
@@ -0,0 +1,728 @@
+use std::cmp::{Eq, PartialEq};
+use std::fmt::{Debug, Display, Formatter, Result};
+use std::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+#[derive(Debug, Clone)]
+pub struct Matrix<T> {
+    rows: u32,
+    cols: u32,
+    data: Vec<T>,
+}
+
+impl<T: Clone + Default> Matrix<T> {
+    pub fn new(rows: u32, cols: u32) -> Matrix<T> {
+        let init = T::default();
+        Matrix {
+            rows,
+            cols,
+            data: vec![init; (rows * cols) as usize],
+        }
+    }
+}
+
+impl<T> Matrix<T> {
+    /// Takes the elements row by row.
+    pub fn from_vec(rows: u32, cols: u32, data: Vec<T>) -> Matrix<T> {
+        assert_eq!((rows * cols) as usize, data.len());
+        Matrix { rows, cols, data }
+    }
+
+    pub fn from_fn(rows: u32, cols: u32, mut f: impl FnMut(u32, u32) -> T) -> Matrix<T> {
+        let mut data = Vec::with_capacity((rows * cols) as usize);
+        for row in 0..rows {
+            for col in 0..cols {
+                data.push(f(row, col));
+            }
+        }
+        Matrix { rows, cols, data }
+    }
+
+    pub fn rows(&self) -> u32 {
+        self.rows
+    }
+
+    pub fn cols(&self) -> u32 {
+        self.cols
+    }
+
+    fn index(&self, row: u32, col: u32) -> usize {
+        assert!(row < self.rows);
+        assert!(col < self.cols);
+        (row * self.cols + col) as usize
+    }
+
+    pub fn set(&mut self, row: u32, col: u32, val: T) {
+        let index = self.index(row, col);
+        self.data[index] = val;
+    }
+
+    pub fn get(&self, row: u32, col: u32) -> &T {
+        &self.data[self.index(row, col)]
+    }
+
+    pub fn get_mut(&mut self, row: u32, col: u32) -> &mut T {
+        let index = self.index(row, col);
+        &mut self.data[index]
+    }
+
+    pub fn row(&self, row: u32) -> &[T] {
+        assert!(row < self.rows);
+        let start = (row * self.cols) as usize;
+        &self.data[start..start + self.cols as usize]
+    }
+
+    pub fn col(&self, col: u32) -> impl Iterator<Item = &T> {
+        assert!(col < self.cols);
+        self.data
+            .iter()
+            .skip(col as usize)
+            .step_by(self.cols as usize)
+    }
+
+    pub fn iter_rows(&self) -> impl Iterator<Item = &[T]> {
+        (0..self.rows).map(|row| self.row(row))
+    }
+
+    pub fn iter_cols(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.cols).map(|col| self.col(col))
+    }
+}
+
+impl<T: Debug> Display for Matrix<T> {
+    fn fmt(&self, f: &mut Formatter) -> Result {
+        write!(f, "{}x{}-Matrix({:?})", self.rows, self.cols, self.data)
+    }
+}
+
+impl<T> Matrix<T> {
+    fn size(&self) -> (u32, u32) {
+        (self.rows, self.cols)
+    }
+
+    fn map_ref(&self, f: impl FnMut(&T) -> T) -> Matrix<T> {
+        Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            data: self.data.iter().map(f).collect(),
+        }
+    }
+}
+
+// combines the elements of two matrices of the same size, each side can be
+// owned or borrowed
+fn zip_elements<T, A, B>(
+    size: (u32, u32),
+    rhs_size: (u32, u32),
+    lhs: impl IntoIterator<Item = A>,
+    rhs: impl IntoIterator<Item = B>,
+    mut f: impl FnMut(A, B) -> T,
+) -> Matrix<T> {
+    assert!(size == rhs_size);
+    Matrix {
+        rows: size.0,
+        cols: size.1,
+        data: lhs.into_iter().zip(rhs).map(|(a, b)| f(a, b)).collect(),
+    }
+}
+
+// Element wise operators, the elements of owned operands are moved and only
+// those of borrowed ones are cloned.
+macro_rules! elementwise_binop {
+    (impl $Op:ident, $method:ident) => {
+        impl<T: Clone + $Op<Output = T>> $Op for &Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: &Matrix<T>) -> Matrix<T> {
+                zip_elements(self.size(), rhs.size(), &self.data, &rhs.data, |a, b| {
+                    a.clone().$method(b.clone())
+                })
+            }
+        }
+
+        impl<T: $Op<Output = T>> $Op for Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: Matrix<T>) -> Matrix<T> {
+                zip_elements(self.size(), rhs.size(), self.data, rhs.data, T::$method)
+            }
+        }
+
+        impl<T: Clone + $Op<Output = T>> $Op<&Matrix<T>> for Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: &Matrix<T>) -> Matrix<T> {
+                zip_elements(self.size(), rhs.size(), self.data, &rhs.data, |a, b| {
+                    a.$method(b.clone())
+                })
+            }
+        }
+
+        impl<T: Clone + $Op<Output = T>> $Op<Matrix<T>> for &Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: Matrix<T>) -> Matrix<T> {
+                zip_elements(self.size(), rhs.size(), &self.data, rhs.data, |a, b| {
+                    a.clone().$method(b)
+                })
+            }
+        }
+    };
+}
+
+macro_rules! elementwise_assign {
+    (impl $Op:ident, $method:ident) => {
+        impl<T: Clone + $Op> $Op<&Matrix<T>> for Matrix<T> {
+            fn $method(&mut self, rhs: &Matrix<T>) {
+                assert!(self.size() == rhs.size());
+                for (a, b) in self.data.iter_mut().zip(&rhs.data) {
+                    a.$method(b.clone());
+                }
+            }
+        }
+
+        impl<T: $Op> $Op for Matrix<T> {
+            fn $method(&mut self, rhs: Matrix<T>) {
+                assert!(self.size() == rhs.size());
+                for (a, b) in self.data.iter_mut().zip(rhs.data) {
+                    a.$method(b);
+                }
+            }
+        }
+    };
+}
+
+elementwise_binop!(impl Add, add);
+elementwise_assign!(impl AddAssign, add_assign);
+elementwise_binop!(impl Sub, sub);
+elementwise_assign!(impl SubAssign, sub_assign);
+
+impl<T: Clone + Neg<Output = T>> Neg for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn neg(self) -> Matrix<T> {
+        self.map_ref(|a| -a.clone())
+    }
+}
+
+impl<T: Neg<Output = T>> Neg for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn neg(self) -> Matrix<T> {
+        Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            data: self.data.into_iter().map(|a| -a).collect(),
+        }
+    }
+}
+
+// The product uses every element several times, so it clones them anyway and
+// the operators on owned matrices use the ones on references.
+macro_rules! forward_binop {
+    (impl $Op:ident, $method:ident where T: $($bound:tt)+) => {
+        impl<T: $($bound)+> $Op for Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: Matrix<T>) -> Matrix<T> {
+                (&self).$method(&rhs)
+            }
+        }
+
+        impl<T: $($bound)+> $Op<&Matrix<T>> for Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: &Matrix<T>) -> Matrix<T> {
+                (&self).$method(rhs)
+            }
+        }
+
+        impl<T: $($bound)+> $Op<Matrix<T>> for &Matrix<T> {
+            type Output = Matrix<T>;
+
+            fn $method(self, rhs: Matrix<T>) -> Matrix<T> {
+                self.$method(&rhs)
+            }
+        }
+    };
+}
+
+macro_rules! forward_assign {
+    (impl $Op:ident, $method:ident where T: $($bound:tt)+) => {
+        impl<T: $($bound)+> $Op for Matrix<T> {
+            fn $method(&mut self, rhs: Matrix<T>) {
+                self.$method(&rhs);
+            }
+        }
+    };
+}
+
+impl<T: Clone + Mul<Output = T> + Add<Output = T>> Mul for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn mul(self, _rhs: &Matrix<T>) -> Matrix<T> {
+        assert!(self.cols == _rhs.rows);
+        let mut data: Vec<T> = Vec::with_capacity((self.rows * _rhs.cols) as usize);
+
+        for row in 0..self.rows {
+            for col in 0.._rhs.cols {
+                let mut new_val = self.get(row, 0).clone() * _rhs.get(0, col).clone();
+
+                for ii in 1..self.cols {
+                    let mult_output = self.get(row, ii).clone() * _rhs.get(ii, col).clone();
+                    new_val = new_val + mult_output;
+                }
+
+                data.push(new_val);
+            }
+        }
+
+        Matrix {
+            rows: self.rows,
+            cols: _rhs.cols,
+            data,
+        }
+    }
+}
+
+forward_binop!(impl Mul, mul where T: Clone + Mul<Output = T> + Add<Output = T>);
+
+impl<T: Clone + Mul<Output = T> + Add<Output = T>> MulAssign<&Matrix<T>> for Matrix<T> {
+    fn mul_assign(&mut self, rhs: &Matrix<T>) {
+        *self = &*self * rhs;
+    }
+}
+
+forward_assign!(impl MulAssign, mul_assign where T: Clone + Mul<Output = T> + Add<Output = T>);
+
+// multiplication with a scalar
+impl<T: Clone + Mul<Output = T>> Mul<T> for &Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn mul(self, rhs: T) -> Matrix<T> {
+        self.map_ref(|a| a.clone() * rhs.clone())
+    }
+}
+
+impl<T: Clone + Mul<Output = T>> Mul<T> for Matrix<T> {
+    type Output = Matrix<T>;
+
+    fn mul(self, rhs: T) -> Matrix<T> {
+        Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            data: self.data.into_iter().map(|a| a * rhs.clone()).collect(),
+        }
+    }
+}
+
+impl<T: Clone + MulAssign> MulAssign<T> for Matrix<T> {
+    fn mul_assign(&mut self, rhs: T) {
+        for a in &mut self.data {
+            *a *= rhs.clone();
+        }
+    }
+}
+
+impl<T: PartialEq> PartialEq for Matrix<T> {
+    fn eq(&self, other: &Matrix<T>) -> bool {
+        if self.rows != other.rows {
+            return false;
+        }
+        if self.cols != other.cols {
+            return false;
+        }
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if *self.get(row, col) != *other.get(row, col) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+}
+
+impl<T: PartialEq> Eq for Matrix<T> {}
+
+/// Creates a matrix row by row, rows are separated by `;` and all need the
+/// same number of items.
+#[macro_export]
+macro_rules! mmatrix(($($($item:expr),+);*) => ({
+  let data = ::std::vec![$($($item,)+)*];
+  // counted without evaluating the items a second time
+  let row_lens = [$([$(stringify!($item)),+].len()),*];
+  assert!(
+    row_lens.iter().all(|&len| len == row_lens[0]),
+    "rows of different lengths"
+  );
+  $crate::Matrix::from_vec(row_lens.len() as u32, row_lens[0] as u32, data)
+}));
+
+#[cfg(test)]
+mod tests {
+    use super::Matrix;
+
+    #[test]
+    fn new() {
+        let m = Matrix::new(3, 4);
+        assert_eq!(3, m.rows);
+        assert_eq!(4, m.cols);
+        for i in 0..3 {
+            for j in 0..4 {
+                assert_eq!(0, *m.get(i, j));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn get_range_test_rows() {
+        let m: Matrix<i32> = Matrix::new(4, 5);
+        m.get(5, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn get_range_test_cols() {
+        let m: Matrix<i32> = Matrix::new(4, 5);
+        m.get(0, 6);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn set_range_test_rows() {
+        let mut m: Matrix<i32> = Matrix::new(4, 5);
+        m.set(5, 0, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn set_range_test_cols() {
+        let mut m: Matrix<i32> = Matrix::new(4, 5);
+        m.set(0, 6, 0);
+    }
+
+    #[test]
+    fn set_get() {
+        let mut m: Matrix<i32> = Matrix::new(4, 4);
+        m.set(0, 0, 3);
+        assert_eq![3, *m.get(0, 0)];
+        m.set(1, 1, 6);
+        assert_eq![6, *m.get(1, 1)];
+        m.set(2, 2, 8);
+        assert_eq![8, *m.get(2, 2)];
+        m.set(3, 3, -10);
+        assert_eq![-10, *m.get(3, 3)];
+        m.set(0, 3, -100);
+        assert_eq![-100, *m.get(0, 3)];
+        m.set(3, 0, -400);
+        assert_eq![-400, *m.get(3, 0)];
+    }
+
+    #[test]
+    fn mmatrix() {
+        //    let m = mmatrix![];
+        //    assert_eq![0, m.rows];
+        //    assert_eq![0, m.cols];
+
+        let m1 = mmatrix![3];
+        assert_eq![1, m1.rows];
+        assert_eq![1, m1.cols];
+        assert_eq![3, *m1.get(0, 0)];
+
+        let m2 = mmatrix![1, 2, 3];
+        assert_eq![1, m2.rows];
+        assert_eq![3, m2.cols];
+        assert_eq![1, *m2.get(0, 0)];
+        assert_eq![2, *m2.get(0, 1)];
+        assert_eq![3, *m2.get(0, 2)];
+
+        let m3 = mmatrix![1;2;3];
+        assert_eq![3, m3.rows];
+        assert_eq![1, m3.cols];
+        assert_eq![1, *m3.get(0, 0)];
+        assert_eq![2, *m3.get(1, 0)];
+        assert_eq![3, *m3.get(2, 0)];
+
+        let m4 = mmatrix![1,2,3;4,5,6];
+        assert_eq![2, m4.rows];
+        assert_eq![3, m4.cols];
+        assert_eq![1, *m4.get(0, 0)];
+        assert_eq![2, *m4.get(0, 1)];
+        assert_eq![3, *m4.get(0, 2)];
+        assert_eq![4, *m4.get(1, 0)];
+        assert_eq![5, *m4.get(1, 1)];
+        assert_eq![6, *m4.get(1, 2)];
+    }
+
+    #[test]
+    fn mmatrix_brackets() {
+        let m = mmatrix!(1,2,3;4,5,6;7,8,9);
+        let mm = mmatrix![1,2,3;4,5,6;7,8,9];
+        assert_eq!(m, mm);
+    }
+
+    #[test]
+    fn eq() {
+        let m0 = mmatrix!(1, 2);
+        let m1 = mmatrix!(2, 3);
+        let m2 = mmatrix!(2;3);
+        let m3 = mmatrix!(1,3,3;4,7,7);
+        let m4 = mmatrix!(2,3,3;4,7,7);
+        assert_eq!(m0, m0);
+        assert_eq!(m1, m1);
+        assert_eq!(m2, m2);
+        assert_eq!(m3, m3);
+        assert_eq!(m4, m4);
+        assert!(m0 != m1);
+        assert!(m0 != m2);
+        assert!(m0 != m3);
+        assert!(m0 != m4);
+        assert!(m1 != m2);
+        assert!(m1 != m3);
+        assert!(m1 != m4);
+        assert!(m2 != m3);
+        assert!(m2 != m4);
+        assert!(m3 != m4);
+    }
+
+    #[test]
+    fn scale() {
+        let m0 = mmatrix!(1,2;3,4);
+        let m1 = mmatrix!(2,0;0,2);
+        let result = m0 * m1;
+        assert_eq!(mmatrix![2,4;6,8], result);
+    }
+
+    #[test]
+    fn mult_4x4_matrix() {
+        assert_eq!(
+            mmatrix![19, 22; 43, 50],
+            mmatrix!(1,2;3,4) * mmatrix!(5,6;7,8)
+        );
+    }
+
+    #[test]
+    fn select_vector_from_matrix() {
+        assert_eq!(mmatrix![1;3], mmatrix!(1,2;3,4) * mmatrix!(1;0));
+    }
+
+    #[test]
+    fn mult_float() {
+        let m0 = mmatrix![2.0, 3.0; 4.0, 5.0];
+        assert_eq!(mmatrix![16.0, 21.0; 28.0, 37.0], m0.clone() * m0);
+    }
+
+    #[test]
+    fn mult_float_by_reference() {
+        let m0 = mmatrix![2.0, 3.0; 4.0, 5.0];
+        assert_eq!(mmatrix![16.0, 21.0; 28.0, 37.0], &m0 * &m0);
+    }
+
+    #[test]
+    fn display() {
+        let m0 = mmatrix!(1,2,3;4,5,6;7,8,9);
+        let s = format!("{}", m0);
+        assert_eq!("3x3-Matrix([1, 2, 3, 4, 5, 6, 7, 8, 9])", s);
+    }
+
+    #[test]
+    fn from_vec_and_from_fn() {
+        let m = Matrix::from_vec(2, 3, vec![0, 1, 2, 10, 11, 12]);
+        assert_eq!(2, m.rows());
+        assert_eq!(3, m.cols());
+        assert_eq!(m, Matrix::from_fn(2, 3, |row, col| row * 10 + col));
+        assert_eq!(mmatrix![0, 1, 2; 10, 11, 12], m);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion `left == right` failed")]
+    fn from_vec_wrong_length() {
+        Matrix::from_vec(2, 2, vec![1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "rows of different lengths")]
+    fn mmatrix_ragged_rows() {
+        mmatrix![1, 2, 3; 4];
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut m = mmatrix![1, 2; 3, 4];
+        *m.get_mut(1, 0) += 10;
+        assert_eq!(mmatrix![1, 2; 13, 4], m);
+    }
+
+    #[test]
+    fn rows_and_cols() {
+        let m = mmatrix![1, 2, 3; 4, 5, 6];
+        assert_eq!(&[4, 5, 6], m.row(1));
+        assert_eq!(vec![&2, &5], m.col(1).collect::<Vec<_>>());
+        assert_eq!(
+            vec![&[1, 2, 3][..], &[4, 5, 6][..]],
+            m.iter_rows().collect::<Vec<_>>()
+        );
+        let cols: Vec<Vec<i32>> = m.iter_cols().map(|col| col.copied().collect()).collect();
+        assert_eq!(vec![vec![1, 4], vec![2, 5], vec![3, 6]], cols);
+    }
+
+    #[test]
+    fn mmatrix_evaluates_items_once() {
+        let mut calls = 0;
+        let mut next = || {
+            calls += 1;
+            calls
+        };
+        let m = mmatrix![next(), next(); next(), next()];
+        assert_eq!(mmatrix![1, 2; 3, 4], m);
+        assert_eq!(4, calls);
+    }
+
+    #[test]
+    fn add() {
+        let m0 = mmatrix![1, 2; 3, 4];
+        let m1 = mmatrix![10, 20; 30, 40];
+        assert_eq!(mmatrix![11, 22; 33, 44], &m0 + &m1);
+        assert_eq!(mmatrix![11, 22; 33, 44], m0.clone() + &m1);
+        assert_eq!(mmatrix![11, 22; 33, 44], &m0 + m1.clone());
+        assert_eq!(mmatrix![11, 22; 33, 44], m0 + m1);
+        assert_eq!(
+            mmatrix![1.5, -1.0],
+            mmatrix![1.0, -2.0] + mmatrix![0.5, 1.0]
+        );
+    }
+
+    #[test]
+    fn sub() {
+        let m0 = mmatrix![1, 2; 3, 4];
+        let m1 = mmatrix![10, 20; 30, 40];
+        assert_eq!(mmatrix![9, 18; 27, 36], &m1 - &m0);
+        assert_eq!(mmatrix![-9, -18; -27, -36], m0 - m1);
+        assert_eq!(
+            mmatrix![0.5; -3.0],
+            mmatrix![1.0; -2.0] - mmatrix![0.5; 1.0]
+        );
+    }
+
+    #[test]
+    fn neg() {
+        let m = mmatrix![1, -2; 0, 4];
+        assert_eq!(mmatrix![-1, 2; 0, -4], -&m);
+        assert_eq!(mmatrix![-1, 2; 0, -4], -m);
+        assert_eq!(mmatrix![-0.5, 2.0], -mmatrix![0.5, -2.0]);
+    }
+
+    #[test]
+    fn mul_scalar() {
+        let m = mmatrix![1, 2; 3, 4];
+        assert_eq!(mmatrix![3, 6; 9, 12], &m * 3);
+        assert_eq!(mmatrix![-1, -2; -3, -4], m * -1);
+        assert_eq!(mmatrix![0.5, 1.0], mmatrix![1.0, 2.0] * 0.5);
+    }
+
+    #[test]
+    fn mul_by_reference() {
+        let m0 = mmatrix!(1,2;3,4);
+        let m1 = mmatrix!(5,6;7,8);
+        let product = mmatrix![19, 22; 43, 50];
+        assert_eq!(product, &m0 * &m1);
+        assert_eq!(product, m0.clone() * &m1);
+        assert_eq!(product, &m0 * m1.clone());
+        // the inputs are still there
+        assert_eq!(mmatrix![7, 10; 15, 22], &m0 * &m0);
+    }
+
+    #[test]
+    fn assign() {
+        let mut m = mmatrix![1, 2; 3, 4];
+        m += &mmatrix![1, 1; 1, 1];
+        assert_eq!(mmatrix![2, 3; 4, 5], m);
+        m -= mmatrix![2, 2; 2, 2];
+        assert_eq!(mmatrix![0, 1; 2, 3], m);
+        m *= 2;
+        assert_eq!(mmatrix![0, 2; 4, 6], m);
+        m *= &mmatrix![1, 0; 0, 1];
+        assert_eq!(mmatrix![0, 2; 4, 6], m);
+        m *= mmatrix![1; 1];
+        assert_eq!(mmatrix![2; 10], m);
+
+        let mut f = mmatrix![1.0, 2.0];
+        f += mmatrix![0.5, 0.5];
+        f -= &mmatrix![1.0, 1.0];
+        f *= 2.0;
+        f *= mmatrix![1.0; 1.0];
+        assert_eq!(mmatrix![4.0], f);
+    }
+
+    #[test]
+    fn owned_operands_are_moved() {
+        // no Clone, so the owned forms can't clone the elements
+        #[derive(Debug, PartialEq)]
+        struct Cents(i64);
+
+        impl std::ops::Add for Cents {
+            type Output = Cents;
+
+            fn add(self, rhs: Cents) -> Cents {
+                Cents(self.0 + rhs.0)
+            }
+        }
+
+        impl std::ops::SubAssign for Cents {
+            fn sub_assign(&mut self, rhs: Cents) {
+                self.0 -= rhs.0;
+            }
+        }
+
+        let mut m = mmatrix![Cents(1), Cents(2)] + mmatrix![Cents(10), Cents(20)];
+        assert_eq!(mmatrix![Cents(11), Cents(22)], m);
+        m -= mmatrix![Cents(1), Cents(1)];
+        assert_eq!(mmatrix![Cents(10), Cents(21)], m);
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn add_mismatched() {
+        let _ = mmatrix![1, 2] + mmatrix![1; 2];
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn add_mismatched_float() {
+        let _ = &mmatrix![1.0, 2.0] + &mmatrix![1.0, 2.0, 3.0];
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn sub_mismatched() {
+        let _ = &mmatrix![1, 2; 3, 4] - &mmatrix![1, 2];
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn add_assign_mismatched() {
+        let mut m = mmatrix![1, 2];
+        m += mmatrix![1];
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn sub_assign_mismatched() {
+        let mut m = mmatrix![1.0; 2.0];
+        m -= &mmatrix![1.0, 2.0];
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn mul_mismatched() {
+        let _ = &mmatrix![1, 2] * &mmatrix![1, 2];
+    }
+
+    #[test]
+    #[should_panic(expected = "assertion failed")]
+    fn mul_assign_mismatched() {
+        let mut m = mmatrix![1.0, 2.0; 3.0, 4.0];
+        m *= mmatrix![1.0, 2.0];
+    }
+}
@@ -0,0 +1,93 @@
+//! A bounded queue shared by producers and consumers. The queue is locked
+//! first, the statistics second, and waiting on the queue keeps the token
+//! that allows to take the statistics lock afterwards.
+
+use std::collections::VecDeque;
+use std::thread;
+
+use type_state::{NoLocksHeld, PriorityCondvar, PriorityMutex, use_priority};
+
+const CAPACITY: usize = 4;
+const PRODUCERS: usize = 3;
+const ITEMS: usize = 100;
+
+struct BoundedQueue<'a> {
+    items: PriorityMutex<'a, VecDeque<usize>, (), 1>,
+    not_empty: PriorityCondvar,
+    not_full: PriorityCondvar,
+    // highest queue length seen, number of items consumed
+    stats: PriorityMutex<'a, (usize, usize), (), 2>,
+}
+
+impl BoundedQueue<'_> {
+    fn push(&self, item: usize) {
+        let mut root = NoLocksHeld::root();
+        let mut locked = self.items.lock(use_priority(&mut root)).unwrap();
+        while locked.1.len() == CAPACITY {
+            locked = self.not_full.wait(locked).unwrap();
+        }
+        let (mut token, mut items) = locked;
+        items.push_back(item);
+        let (_, mut stats) = self.stats.lock(use_priority(&mut token)).unwrap();
+        stats.0 = stats.0.max(items.len());
+        self.not_empty.notify_one();
+    }
+
+    fn pop(&self) -> usize {
+        let mut root = NoLocksHeld::root();
+        let mut locked = self.items.lock(use_priority(&mut root)).unwrap();
+        while locked.1.is_empty() {
+            locked = self.not_empty.wait(locked).unwrap();
+        }
+        let (mut token, mut items) = locked;
+        let item = items.pop_front().unwrap();
+        self.stats.lock(use_priority(&mut token)).unwrap().1.1 += 1;
+        self.not_full.notify_one();
+        item
+    }
+}
+
+fn main() {
+    let queue = BoundedQueue {
+        items: PriorityMutex::new(VecDeque::new()),
+        not_empty: PriorityCondvar::new(),
+        not_full: PriorityCondvar::new(),
+        stats: PriorityMutex::new((0, 0)),
+    };
+    let sum: usize = thread::scope(|scope| {
+        for producer in 0..PRODUCERS {
+            let queue = &queue;
+            scope.spawn(move || {
+                for item in 0..ITEMS {
+                    queue.push(producer * ITEMS + item);
+                }
+            });
+        }
+        let consumers: Vec<_> = (0..2)
+            .map(|_| {
+                scope.spawn(|| {
+                    (0..PRODUCERS * ITEMS / 2)
+                        .map(|_| queue.pop())
+                        .sum::<usize>()
+                })
+            })
+            .collect();
+        consumers
+            .into_iter()
+            .map(|consumer| consumer.join().unwrap())
+            .sum()
+    });
+
+    let n = PRODUCERS * ITEMS;
+    assert_eq!(n * (n - 1) / 2, sum);
+    let (_, stats) = queue
+        .stats
+        .lock(use_priority(&mut NoLocksHeld::root()))
+        .unwrap();
+    assert!(stats.0 <= CAPACITY);
+    assert_eq!(n, stats.1);
+    println!(
+        "consumed {} items, the queue held at most {}",
+        stats.1, stats.0
+    );
+}
@@ -0,0 +1,169 @@
+use std::marker::PhantomData;
+
+use tokio::sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::mutex_ordering::{HeldPriority, TakenLockPriority, check_order};
+
+/// The tokio counterpart of `PriorityMutex`, the guard can be held across
+/// `.await` without blocking the executor.
+///
+/// ```compile_fail
+/// use type_state::{AsyncPriorityMutex, NoLocksHeld, use_priority};
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let mut root = NoLocksHeld::root();
+///     let m1 = AsyncPriorityMutex::<'_, (), (), 1>::new(());
+///     let m2 = AsyncPriorityMutex::<'_, (), (), 2>::new(());
+///     let (mut token2, _guard2) = m2.lock(use_priority(&mut root)).await;
+///     let (_token1, _guard1) = m1.lock(use_priority(&mut token2)).await;
+/// }
+/// ```
+pub struct AsyncPriorityMutex<'a, T: ?Sized, U, const PRIORITY: usize> {
+    previous: PhantomData<&'a mut U>,
+    mutex: Mutex<T>,
+}
+
+impl<T, U, const PRIORITY: usize> AsyncPriorityMutex<'_, T, U, PRIORITY> {
+    pub const fn new(value: T) -> Self {
+        AsyncPriorityMutex {
+            previous: PhantomData,
+            mutex: Mutex::const_new(value),
+        }
+    }
+}
+
+impl<T: ?Sized, U, const PRIORITY: usize> AsyncPriorityMutex<'_, T, U, PRIORITY> {
+    pub async fn lock<'c, H: HeldPriority>(
+        &self,
+        _previous_priority: PhantomData<&'c mut H>,
+    ) -> (TakenLockPriority<'c, Self, PRIORITY>, MutexGuard<'_, T>)
+    where
+        Self: 'c,
+    {
+        const { check_order(H::PRIORITY, PRIORITY) }
+        (
+            TakenLockPriority {
+                phantom: PhantomData,
+            },
+            self.mutex.lock().await,
+        )
+    }
+}
+
+/// The tokio counterpart of `PriorityRwLock`.
+///
+/// ```compile_fail
+/// use type_state::{AsyncPriorityRwLock, NoLocksHeld, use_priority};
+///
+/// #[tokio::main(flavor = "current_thread")]
+/// async fn main() {
+///     let mut root = NoLocksHeld::root();
+///     let l1 = AsyncPriorityRwLock::<'_, (), (), 1>::new(());
+///     let l2 = AsyncPriorityRwLock::<'_, (), (), 2>::new(());
+///     let (mut token2, _guard2) = l2.write(use_priority(&mut root)).await;
+///     let (_token1, _guard1) = l1.read(use_priority(&mut token2)).await;
+/// }
+/// ```
+pub struct AsyncPriorityRwLock<'a, T: ?Sized, U, const PRIORITY: usize> {
+    previous: PhantomData<&'a mut U>,
+    lock: RwLock<T>,
+}
+
+impl<T, U, const PRIORITY: usize> AsyncPriorityRwLock<'_, T, U, PRIORITY> {
+    pub const fn new(value: T) -> Self {
+        AsyncPriorityRwLock {
+            previous: PhantomData,
+            lock: RwLock::const_new(value),
+        }
+    }
+}
+
+impl<T: ?Sized, U, const PRIORITY: usize> AsyncPriorityRwLock<'_, T, U, PRIORITY> {
+    pub async fn read<'c, H: HeldPriority>(
+        &self,
+        _previous_priority: PhantomData<&'c mut H>,
+    ) -> (
+        TakenLockPriority<'c, Self, PRIORITY>,
+        RwLockReadGuard<'_, T>,
+    )
+    where
+        Self: 'c,
+    {
+        const { check_order(H::PRIORITY, PRIORITY) }
+        (
+            TakenLockPriority {
+                phantom: PhantomData,
+            },
+            self.lock.read().await,
+        )
+    }
+
+    pub async fn write<'c, H: HeldPriority>(
+        &self,
+        _previous_priority: PhantomData<&'c mut H>,
+    ) -> (
+        TakenLockPriority<'c, Self, PRIORITY>,
+        RwLockWriteGuard<'_, T>,
+    )
+    where
+        Self: 'c,
+    {
+        const { check_order(H::PRIORITY, PRIORITY) }
+        (
+            TakenLockPriority {
+                phantom: PhantomData,
+            },
+            self.lock.write().await,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{AsyncPriorityMutex, AsyncPriorityRwLock};
+    use crate::mutex_ordering::{NoLocksHeld, use_priority};
+
+    #[tokio::test]
+    async fn held_across_await() {
+        let mut root = NoLocksHeld::root();
+        let names = AsyncPriorityRwLock::<'_, Vec<&str>, (), 1>::new(vec!["a"]);
+        let count = AsyncPriorityMutex::<'_, usize, (), 2>::new(0);
+        {
+            let (mut token, mut names) = names.write(use_priority(&mut root)).await;
+            tokio::task::yield_now().await;
+            names.push("b");
+            let (_, mut count) = count.lock(use_priority(&mut token)).await;
+            tokio::task::yield_now().await;
+            *count = names.len();
+        }
+        let (mut token, names) = names.read(use_priority(&mut root)).await;
+        let (_, count) = count.lock(use_priority(&mut token)).await;
+        assert_eq!(names.len(), *count);
+    }
+
+    #[tokio::test]
+    async fn shared_between_tasks() {
+        let counter = Arc::new(AsyncPriorityMutex::<'static, usize, (), 1>::new(0));
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let counter = counter.clone();
+                tokio::spawn(async move {
+                    let mut root = NoLocksHeld::root();
+                    for _ in 0..100 {
+                        let (_, mut count) = counter.lock(use_priority(&mut root)).await;
+                        tokio::task::yield_now().await;
+                        *count += 1;
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        let (_, count) = counter.lock(use_priority(&mut NoLocksHeld::root())).await;
+        assert_eq!(400, *count);
+    }
+}
@@ -0,0 +1,276 @@
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio::sync::Mutex;
+
+/// Something that can be sent as the body of an `HttpResponse`.
+pub trait ResponseBody {
+    /// `Content-Type` that is sent unless one was set explicitly.
+    fn content_type(&self) -> &str;
+
+    fn content_length(&self) -> u64;
+
+    /// Reads the body from `offset` on into `buf`, returns 0 at the end.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize>;
+
+    /// Like `read_at`, but must not block the async runtime. Bodies that wait
+    /// for the disk or the network override it.
+    fn read_at_async(
+        &self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> impl Future<Output = io::Result<usize>> + Send {
+        // read right away, so the future does not need Self to be Sync
+        let read = self.read_at(offset, buf);
+        async move { read }
+    }
+}
+
+const TEXT: &str = "text/plain; charset=utf-8";
+const BINARY: &str = "application/octet-stream";
+
+fn read_slice_at(bytes: &[u8], offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+    let rest = usize::try_from(offset)
+        .ok()
+        .and_then(|offset| bytes.get(offset..))
+        .unwrap_or_default();
+    let n = rest.len().min(buf.len());
+    buf[..n].copy_from_slice(&rest[..n]);
+    Ok(n)
+}
+
+impl ResponseBody for &str {
+    fn content_type(&self) -> &str {
+        TEXT
+    }
+
+    fn content_length(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        read_slice_at(self.as_bytes(), offset, buf)
+    }
+}
+
+impl ResponseBody for String {
+    fn content_type(&self) -> &str {
+        TEXT
+    }
+
+    fn content_length(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        read_slice_at(self.as_bytes(), offset, buf)
+    }
+}
+
+impl ResponseBody for &[u8] {
+    fn content_type(&self) -> &str {
+        BINARY
+    }
+
+    fn content_length(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        read_slice_at(self, offset, buf)
+    }
+}
+
+impl ResponseBody for Vec<u8> {
+    fn content_type(&self) -> &str {
+        BINARY
+    }
+
+    fn content_length(&self) -> u64 {
+        self.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        read_slice_at(self, offset, buf)
+    }
+}
+
+/// A value serialized as JSON.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Json(Vec<u8>);
+
+impl Json {
+    pub fn new<T: Serialize + ?Sized>(value: &T) -> serde_json::Result<Json> {
+        serde_json::to_vec(value).map(Json)
+    }
+}
+
+impl ResponseBody for Json {
+    fn content_type(&self) -> &str {
+        "application/json"
+    }
+
+    fn content_length(&self) -> u64 {
+        self.0.len() as u64
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        read_slice_at(&self.0, offset, buf)
+    }
+}
+
+/// A file that is read from disk while the response is sent.
+#[derive(Debug)]
+pub struct FileBody {
+    file: File,
+    // opened on the first async read and kept with its position, so sending
+    // does not reopen the file for every chunk
+    async_file: Mutex<Option<(tokio::fs::File, u64)>>,
+    len: u64,
+    content_type: &'static str,
+}
+
+impl FileBody {
+    /// Opens `path`, the content type is guessed from its extension.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<FileBody> {
+        let path = path.as_ref();
+        let file = File::open(path)?;
+        let len = file.metadata()?.len();
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let content_type = match extension.as_str() {
+            "html" | "htm" => "text/html; charset=utf-8",
+            "txt" => TEXT,
+            "css" => "text/css",
+            "js" => "text/javascript",
+            "json" => "application/json",
+            "png" => "image/png",
+            "jpg" | "jpeg" => "image/jpeg",
+            "gif" => "image/gif",
+            "svg" => "image/svg+xml",
+            "gz" => "application/gzip",
+            _ => BINARY,
+        };
+        Ok(FileBody {
+            file,
+            async_file: Mutex::new(None),
+            len,
+            content_type,
+        })
+    }
+
+    // stops at the length the file had when it was opened, so the body
+    // matches the Content-Length even if the file grows
+    fn chunk_len(&self, offset: u64, buf: &[u8]) -> usize {
+        let remaining = self.len.saturating_sub(offset);
+        buf.len()
+            .min(usize::try_from(remaining).unwrap_or(usize::MAX))
+    }
+}
+
+impl ResponseBody for FileBody {
+    fn content_type(&self) -> &str {
+        self.content_type
+    }
+
+    fn content_length(&self) -> u64 {
+        self.len
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.chunk_len(offset, buf);
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(offset))?;
+        file.read(&mut buf[..n])
+    }
+
+    // tokio runs the seek and the read on its blocking thread pool
+    async fn read_at_async(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.chunk_len(offset, buf);
+        let mut async_file = self.async_file.lock().await;
+        let (file, position) = match &mut *async_file {
+            Some(opened) => opened,
+            None => {
+                async_file.insert((tokio::fs::File::from_std(self.file.try_clone()?), u64::MAX))
+            }
+        };
+        if *position != offset {
+            *position = file.seek(SeekFrom::Start(offset)).await?;
+        }
+        let read = file.read(&mut buf[..n]).await?;
+        *position += read as u64;
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FileBody, Json, ResponseBody};
+
+    fn read_all(body: &impl ResponseBody) -> Vec<u8> {
+        let mut all = Vec::new();
+        let mut buf = [0; 3];
+        loop {
+            let n = body.read_at(all.len() as u64, &mut buf).unwrap();
+            if n == 0 {
+                return all;
+            }
+            all.extend_from_slice(&buf[..n]);
+        }
+    }
+
+    #[test]
+    fn in_memory_bodies() {
+        assert_eq!(b"blub".to_vec(), read_all(&"blub"));
+        assert_eq!(b"blub".to_vec(), read_all(&"blub".to_string()));
+        assert_eq!(vec![0, 255, 13, 10], read_all(&vec![0u8, 255, 13, 10]));
+        assert_eq!(b"".to_vec(), read_all(&&b""[..]));
+        assert_eq!(0, "blub".read_at(10, &mut [0; 4]).unwrap());
+    }
+
+    #[test]
+    fn json_body() {
+        let json = Json::new(&[("a", 1)]).unwrap();
+        assert_eq!(b"[[\"a\",1]]".to_vec(), read_all(&json));
+        assert_eq!(9, json.content_length());
+        assert_eq!("application/json", json.content_type());
+    }
+
+    #[test]
+    fn file_body() {
+        let path = std::env::temp_dir().join(format!("type_state_body_{}.png", std::process::id()));
+        std::fs::write(&path, [0x89, b'P', b'N', b'G', 0, 1, 2]).unwrap();
+        let file = FileBody::open(&path).unwrap();
+        assert_eq!("image/png", file.content_type());
+        assert_eq!(7, file.content_length());
+        assert_eq!(vec![0x89, b'P', b'N', b'G', 0, 1, 2], read_all(&file));
+        // can be read more than once
+        assert_eq!(vec![0x89, b'P', b'N', b'G', 0, 1, 2], read_all(&file));
+        drop(file);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn file_body_async() {
+        let path = std::env::temp_dir().join(format!("type_state_body_{}.txt", std::process::id()));
+        std::fs::write(&path, "abcdefg").unwrap();
+        let file = FileBody::open(&path).unwrap();
+        // grown after opening, the body keeps its length
+        std::fs::write(&path, "abcdefghij").unwrap();
+        let mut buf = [0; 4];
+        assert_eq!(4, file.read_at_async(0, &mut buf).await.unwrap());
+        assert_eq!(b"abcd", &buf);
+        assert_eq!(3, file.read_at_async(4, &mut buf).await.unwrap());
+        assert_eq!(b"efg", &buf[..3]);
+        assert_eq!(0, file.read_at_async(7, &mut buf).await.unwrap());
+        drop(file);
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,64 @@
+#[cfg(test)]
+mod test {
+    use type_state_derive::TypeStateBuilder;
+
+    use crate::html::{Body, HttpResponse};
+    use crate::status::StatusCode;
+
+    // A derived builder with the call order of `HttpResponse`: the status
+    // line first, then any number of headers, then the body. It only mirrors
+    // the order, the headers are plain tuples that are validated when
+    // `into_http` builds the real response, and the body is a `String` because
+    // the derive does not support generic structs.
+    #[derive(TypeStateBuilder)]
+    struct Response {
+        #[builder(ordered)]
+        status: StatusCode,
+        #[builder(optional, each = "header")]
+        headers: Vec<(&'static str, &'static str)>,
+        #[builder(ordered)]
+        body: String,
+    }
+
+    impl Response {
+        fn into_http(self) -> HttpResponse<Body<String>> {
+            let mut response = HttpResponse::default().status(self.status);
+            for (name, value) in self.headers {
+                response = response.header(name, value).unwrap();
+            }
+            response.body(self.body)
+        }
+    }
+
+    #[test]
+    fn converts_to_the_handwritten_response() {
+        let handwritten = HttpResponse::default()
+            .status(StatusCode::OK)
+            .header("Server", "type_state")
+            .unwrap()
+            .header("Cache-Control", "no-cache")
+            .unwrap()
+            .body("hello".to_string());
+        let derived = Response::builder()
+            .status(StatusCode::OK)
+            .header(("Server", "type_state"))
+            .header(("Cache-Control", "no-cache"))
+            .body("hello".to_string())
+            .build();
+        assert_eq!(handwritten, derived.into_http());
+    }
+
+    #[test]
+    fn headers_are_optional() {
+        let derived = Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(String::new())
+            .build();
+        assert_eq!(
+            HttpResponse::default()
+                .status(StatusCode::NOT_FOUND)
+                .body(String::new()),
+            derived.into_http()
+        );
+    }
+}
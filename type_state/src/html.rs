@@ -1,24 +1,162 @@
 // redo html example
 
+use std::error::Error;
 use std::fmt::Display;
+use std::future::Future;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::body::ResponseBody;
+use crate::request::{is_field_value, is_token};
+use crate::status::StatusCode;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum InvalidHeader {
+    Name(String),
+    Value(String),
+    Reason(String),
+}
+
+impl Display for InvalidHeader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InvalidHeader::Name(name) => write!(f, "invalid header name {name:?}"),
+            InvalidHeader::Value(value) => write!(f, "invalid header value {value:?}"),
+            InvalidHeader::Reason(reason) => write!(f, "invalid reason phrase {reason:?}"),
+        }
+    }
+}
+
+impl Error for InvalidHeader {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Connection {
+    KeepAlive,
+    Close,
+}
+
+const BODY_CHUNK_SIZE: usize = 16 * 1024;
 
 pub trait ResponseState {}
-pub trait SendingState {}
-struct Start {}
+pub trait SendingState {
+    /// The head of the response in HTTP/1.1 wire format, empty if it was
+    /// already written.
+    fn head(&self) -> Vec<u8>;
+
+    /// Reads the body from `offset` on into `buf`, returns 0 at the end.
+    fn read_body_at(&self, _offset: u64, _buf: &mut [u8]) -> io::Result<usize> {
+        Ok(0)
+    }
+
+    /// Like `read_body_at`, but without blocking the async runtime.
+    fn read_body_at_async(
+        &self,
+        _offset: u64,
+        _buf: &mut [u8],
+    ) -> impl Future<Output = io::Result<usize>> + Send {
+        async { Ok(0) }
+    }
+}
+mod sealed {
+    use super::Headers;
+
+    // lets the server add Connection and Content-Length before sending
+    pub trait Sealed {
+        fn headers_mut(&mut self) -> &mut Headers;
+    }
+}
+/// A complete response that was not sent yet, only these can be returned by
+/// the handlers of a `Router`. Sealed, only `Headers` and `Body` implement it.
+pub trait RoutableState: ResponseState + SendingState + sealed::Sealed {}
+#[derive(Debug, PartialEq, Eq)]
+pub struct Start {}
 impl ResponseState for Start {}
-struct Headers {
-    status_line: (u8, String),
+#[derive(Debug, PartialEq, Eq)]
+pub struct Headers {
+    status_line: (StatusCode, String),
     header: Vec<(String, String)>,
 }
 impl ResponseState for Headers {}
-impl SendingState for Headers {}
-struct Body {
+impl sealed::Sealed for Headers {
+    fn headers_mut(&mut self) -> &mut Headers {
+        self
+    }
+}
+impl RoutableState for Headers {}
+impl SendingState for Headers {
+    fn head(&self) -> Vec<u8> {
+        let (code, reason) = &self.status_line;
+        let mut head = format!("HTTP/1.1 {code} {reason}\r\n");
+        for (key, value) in &self.header {
+            head.push_str(&format!("{key}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+        head.into_bytes()
+    }
+}
+#[derive(Debug, PartialEq, Eq)]
+pub struct Body<B: ResponseBody> {
     headers: Headers,
-    body: String,
+    body: B,
+}
+impl<B: ResponseBody> ResponseState for Body<B> {}
+impl<B: ResponseBody> sealed::Sealed for Body<B> {
+    fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+}
+impl<B: ResponseBody> RoutableState for Body<B> {}
+impl<B: ResponseBody> SendingState for Body<B> {
+    fn head(&self) -> Vec<u8> {
+        self.headers.head()
+    }
+
+    fn read_body_at(&self, offset: u64, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.body.read_at(offset, buf)?;
+        check_body_len(self.body.content_length(), offset, n)
+    }
+
+    fn read_body_at_async(
+        &self,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> impl Future<Output = io::Result<usize>> + Send {
+        let len = self.body.content_length();
+        let read = self.body.read_at_async(offset, buf);
+        async move { check_body_len(len, offset, read.await?) }
+    }
+}
+// A body that ends before its Content-Length, e.g. a file truncated while it
+// is sent, would leave the client waiting for the missing bytes.
+fn check_body_len(len: u64, offset: u64, n: usize) -> io::Result<usize> {
+    if n == 0 && offset < len {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!("body ended after {offset} of {len} bytes"),
+        ));
+    }
+    Ok(n)
+}
+// The head is written when streaming starts, every write is sent as one
+// chunk. Only finish() terminates the body, so Streaming is no SendingState.
+pub struct Streaming<W: Write> {
+    writer: W,
+    trailers: Vec<(String, String)>,
+}
+impl<W: Write> ResponseState for Streaming<W> {}
+pub struct Finished<W: Write> {
+    writer: W,
+}
+impl<W: Write> ResponseState for Finished<W> {}
+impl<W: Write> SendingState for Finished<W> {
+    fn head(&self) -> Vec<u8> {
+        Vec::new()
+    }
 }
-impl ResponseState for Body {}
-impl SendingState for Body {}
 
+#[derive(Debug, PartialEq, Eq)]
 pub struct HttpResponse<S: ResponseState> {
     _sending_state: S,
 }
@@ -27,18 +165,90 @@ impl<S> HttpResponse<S>
 where
     S: ResponseState + SendingState,
 {
-    pub fn send(self) {}
+    pub fn send<W: Write>(self, writer: &mut W) -> io::Result<()> {
+        self.write_to(writer)?;
+        writer.flush()
+    }
+
+    pub async fn send_async<W: AsyncWrite + Unpin>(self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self._sending_state.head()).await?;
+        let mut chunks = Chunks::new();
+        loop {
+            let (offset, buf) = chunks.next_buf();
+            let n = self._sending_state.read_body_at_async(offset, buf).await?;
+            let Some(chunk) = chunks.filled(n) else {
+                return writer.flush().await;
+            };
+            writer.write_all(chunk).await?;
+        }
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self._sending_state.head())?;
+        let mut chunks = Chunks::new();
+        loop {
+            let (offset, buf) = chunks.next_buf();
+            let n = self._sending_state.read_body_at(offset, buf)?;
+            let Some(chunk) = chunks.filled(n) else {
+                return Ok(());
+            };
+            writer.write_all(chunk)?;
+        }
+    }
 }
 
-impl Display for HttpResponse<Body> {
+// reads the body chunk by chunk, for the sync and the async sender
+struct Chunks {
+    buf: Vec<u8>,
+    offset: u64,
+}
+
+impl Chunks {
+    fn new() -> Chunks {
+        Chunks {
+            buf: vec![0; BODY_CHUNK_SIZE],
+            offset: 0,
+        }
+    }
+
+    // where the next chunk is read to
+    fn next_buf(&mut self) -> (u64, &mut [u8]) {
+        (self.offset, &mut self.buf)
+    }
+
+    // the chunk after `n` bytes were read, None at the end of the body
+    fn filled(&mut self, n: usize) -> Option<&[u8]> {
+        if n == 0 {
+            return None;
+        }
+        self.offset += n as u64;
+        Some(&self.buf[..n])
+    }
+}
+
+impl<S: RoutableState> HttpResponse<S> {
+    pub(crate) fn headers_mut(&mut self) -> &mut Headers {
+        self._sending_state.headers_mut()
+    }
+
+    // the answer to a HEAD request, which describes the body without it
+    pub(crate) async fn send_head_async<W: AsyncWrite + Unpin>(
+        self,
+        writer: &mut W,
+    ) -> io::Result<()> {
+        writer.write_all(&self._sending_state.head()).await?;
+        writer.flush().await
+    }
+}
+
+impl<S> Display for HttpResponse<S>
+where
+    S: ResponseState + SendingState,
+{
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{:?} {:?} {}",
-            self._sending_state.headers.status_line,
-            self._sending_state.headers.header,
-            self._sending_state.body
-        )
+        let mut message = Vec::new();
+        self.write_to(&mut message).map_err(|_| std::fmt::Error)?;
+        write!(f, "{}", String::from_utf8_lossy(&message))
     }
 }
 
@@ -51,47 +261,331 @@ impl Default for HttpResponse<Start> {
 }
 
 impl HttpResponse<Start> {
-    pub fn status_line(self, code: u8, message: &str) -> HttpResponse<Headers> {
+    /// Status line with the standard reason phrase of `code`.
+    pub fn status(self, code: StatusCode) -> HttpResponse<Headers> {
+        let reason = code.canonical_reason().unwrap_or_default();
+        Self::with_status_line(code, reason)
+    }
+
+    /// Status line with a custom reason phrase, which must not contain line
+    /// breaks or other control characters.
+    pub fn status_line(
+        self,
+        code: StatusCode,
+        message: &str,
+    ) -> Result<HttpResponse<Headers>, InvalidHeader> {
+        if !is_field_value(message) {
+            return Err(InvalidHeader::Reason(message.to_string()));
+        }
+        Ok(Self::with_status_line(code, message))
+    }
+
+    fn with_status_line(code: StatusCode, reason: &str) -> HttpResponse<Headers> {
         HttpResponse {
             _sending_state: Headers {
-                status_line: (code, message.to_string()),
+                status_line: (code, reason.to_string()),
                 header: Vec::new(),
             },
         }
     }
 }
 
+fn validate_header(key: &str, value: &str) -> Result<(), InvalidHeader> {
+    if !is_token(key) {
+        return Err(InvalidHeader::Name(key.to_string()));
+    }
+    // leading or trailing whitespace would be stripped by the receiver
+    if !is_field_value(value) || value.trim_matches([' ', '\t']) != value {
+        return Err(InvalidHeader::Value(value.to_string()));
+    }
+    Ok(())
+}
+
+impl Headers {
+    pub(crate) fn status_code(&self) -> StatusCode {
+        self.status_line.0
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<&str> {
+        self.header
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn remove(&mut self, key: &str) {
+        self.header.retain(|(k, _)| !k.eq_ignore_ascii_case(key));
+    }
+
+    // replaces the first header named `key` and drops all others with that name
+    pub(crate) fn set(&mut self, key: &str, value: String) {
+        let Some(first) = self
+            .header
+            .iter()
+            .position(|(k, _)| k.eq_ignore_ascii_case(key))
+        else {
+            self.header.push((key.to_string(), value));
+            return;
+        };
+        self.header[first].1 = value;
+        let rest = self.header.split_off(first + 1);
+        self.header.extend(
+            rest.into_iter()
+                .filter(|(k, _)| !k.eq_ignore_ascii_case(key)),
+        );
+    }
+}
+
 impl HttpResponse<Headers> {
-    pub fn header(mut self, key: &str, value: &str) -> HttpResponse<Headers> {
+    /// Adds a header, even if one with the same name is already set.
+    pub fn header(
+        mut self,
+        key: &str,
+        value: &str,
+    ) -> Result<HttpResponse<Headers>, InvalidHeader> {
+        validate_header(key, value)?;
         self._sending_state
             .header
             .push((key.to_string(), value.to_string()));
+        Ok(self)
+    }
+
+    /// Sets a header, replacing all headers with the same name.
+    pub fn set_header(
+        mut self,
+        key: &str,
+        value: &str,
+    ) -> Result<HttpResponse<Headers>, InvalidHeader> {
+        validate_header(key, value)?;
+        self._sending_state.set(key, value.to_string());
+        Ok(self)
+    }
+
+    /// First header named `key`, compared case-insensitively.
+    pub fn get_header(&self, key: &str) -> Option<&str> {
+        self._sending_state.get(key)
+    }
+
+    pub fn status_code(&self) -> StatusCode {
+        self._sending_state.status_line.0
+    }
+
+    pub fn reason(&self) -> &str {
+        &self._sending_state.status_line.1
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self._sending_state.header
+    }
+
+    pub fn content_type(self, mime_type: &str) -> Result<HttpResponse<Headers>, InvalidHeader> {
+        self.set_header("Content-Type", mime_type)
+    }
+
+    pub fn content_length(mut self, length: u64) -> HttpResponse<Headers> {
+        self._sending_state
+            .set("Content-Length", length.to_string());
+        self
+    }
+
+    pub fn date(mut self, time: SystemTime) -> HttpResponse<Headers> {
+        self._sending_state.set("Date", http_date(time));
+        self
+    }
+
+    pub fn connection(mut self, connection: Connection) -> HttpResponse<Headers> {
+        let value = match connection {
+            Connection::KeepAlive => "keep-alive",
+            Connection::Close => "close",
+        };
+        self._sending_state.set("Connection", value.to_string());
+        self
+    }
+
+    /// Sets the body, `Content-Length` is set to its size and `Content-Type`
+    /// to the type of the body unless it was set before.
+    pub fn body<B: ResponseBody>(mut self, body: B) -> HttpResponse<Body<B>> {
+        if self.get_header("Content-Type").is_none() {
+            self._sending_state
+                .set("Content-Type", body.content_type().to_string());
+        }
+        let headers = self.content_length(body.content_length())._sending_state;
         HttpResponse {
-            _sending_state: self._sending_state,
+            _sending_state: Body { headers, body },
         }
     }
+}
 
-    pub fn body(self, body: &str) -> HttpResponse<Body> {
+impl HttpResponse<Headers> {
+    // a received response, taken as is
+    pub(crate) fn parsed(
+        status_line: (StatusCode, String),
+        header: Vec<(String, String)>,
+    ) -> HttpResponse<Headers> {
+        HttpResponse {
+            _sending_state: Headers {
+                status_line,
+                header,
+            },
+        }
+    }
+
+    // a received body, Content-Length and Content-Type stay as they were sent
+    pub(crate) fn with_parsed_body<B: ResponseBody>(self, body: B) -> HttpResponse<Body<B>> {
         HttpResponse {
             _sending_state: Body {
                 headers: self._sending_state,
-                body: body.to_string(),
+                body,
+            },
+        }
+    }
+}
+
+impl<B: ResponseBody> HttpResponse<Body<B>> {
+    pub fn status_code(&self) -> StatusCode {
+        self._sending_state.headers.status_line.0
+    }
+
+    pub fn reason(&self) -> &str {
+        &self._sending_state.headers.status_line.1
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self._sending_state.headers.header
+    }
+
+    /// First header named `key`, compared case-insensitively.
+    pub fn get_header(&self, key: &str) -> Option<&str> {
+        self._sending_state.headers.get(key)
+    }
+
+    pub fn get_body(&self) -> &B {
+        &self._sending_state.body
+    }
+}
+
+impl HttpResponse<Headers> {
+    /// Writes the head to `writer` and continues with a body that is sent
+    /// in chunks as it is written. `Content-Length` is dropped in favour of
+    /// `Transfer-Encoding: chunked`.
+    pub fn stream<W: Write>(mut self, mut writer: W) -> io::Result<HttpResponse<Streaming<W>>> {
+        self._sending_state.remove("Content-Length");
+        self._sending_state
+            .set("Transfer-Encoding", "chunked".to_string());
+        writer.write_all(&self._sending_state.head())?;
+        Ok(HttpResponse {
+            _sending_state: Streaming {
+                writer,
+                trailers: Vec::new(),
             },
+        })
+    }
+}
+
+impl<W: Write> HttpResponse<Streaming<W>> {
+    /// Adds a trailer field, sent after the last chunk.
+    pub fn trailer(&mut self, key: &str, value: &str) -> Result<(), InvalidHeader> {
+        validate_header(key, value)?;
+        self._sending_state
+            .trailers
+            .push((key.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    /// Ends the body with the last chunk and the trailers.
+    pub fn finish(mut self) -> io::Result<HttpResponse<Finished<W>>> {
+        let mut end = "0\r\n".to_string();
+        for (key, value) in &self._sending_state.trailers {
+            end.push_str(&format!("{key}: {value}\r\n"));
+        }
+        end.push_str("\r\n");
+        self._sending_state.writer.write_all(end.as_bytes())?;
+        self._sending_state.writer.flush()?;
+        Ok(HttpResponse {
+            _sending_state: Finished {
+                writer: self._sending_state.writer,
+            },
+        })
+    }
+}
+
+impl<W: Write> Write for HttpResponse<Streaming<W>> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // an empty chunk would end the body
+        if buf.is_empty() {
+            return Ok(0);
         }
+        let writer = &mut self._sending_state.writer;
+        writer.write_all(format!("{:x}\r\n", buf.len()).as_bytes())?;
+        writer.write_all(buf)?;
+        writer.write_all(b"\r\n")?;
+        Ok(buf.len())
     }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self._sending_state.writer.flush()
+    }
+}
+
+impl<W: Write> HttpResponse<Finished<W>> {
+    /// The writer the response was streamed to.
+    pub fn into_inner(self) -> W {
+        self._sending_state.writer
+    }
+}
+
+/// Formats `time` as IMF-fixdate, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn http_date(time: SystemTime) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let days = seconds / 86400;
+    let (hour, minute, second) = (seconds % 86400 / 3600, seconds % 3600 / 60, seconds % 60);
+
+    // civil_from_days of Howard Hinnant's date algorithms, shifted so that
+    // years start in March and leap days come last
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = (shifted_month + 2) % 12;
+    let year = year_of_era + era * 400 + u64::from(month < 2);
+
+    format!(
+        "{}, {day:02} {} {year} {hour:02}:{minute:02}:{second:02} GMT",
+        WEEKDAYS[(seconds / 86400 % 7) as usize],
+        MONTHS[month as usize],
+    )
 }
 
 #[cfg(test)]
 mod test {
-    use super::HttpResponse;
+    use std::io::{self, Write};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use serde::Serialize;
+
+    use super::{BODY_CHUNK_SIZE, Connection, HttpResponse, InvalidHeader, http_date};
+    use crate::body::{FileBody, Json};
+    use crate::status::StatusCode;
 
     #[test]
     fn create_valid_response() {
         let httpresponse = HttpResponse::default();
         httpresponse
-            .status_line(123, "blub")
-            .header("Length", "123")
+            .status_line(StatusCode::new(123).unwrap(), "blub")
+            .unwrap()
             .header("Spam-value", "666")
+            .unwrap()
             .body("aaaaaaaahhhhh");
     }
 
@@ -99,13 +593,14 @@ mod test {
     fn check_display() {
         let httpresponse = HttpResponse::default();
         let body = httpresponse
-            .status_line(123, "blub")
-            .header("Length", "123")
+            .status_line(StatusCode::new(123).unwrap(), "blub")
+            .unwrap()
             .header("Spam-value", "666")
+            .unwrap()
             .body("aaaaaaaahhhhh");
 
         assert_eq!(
-            "(123, \"blub\") [(\"Length\", \"123\"), (\"Spam-value\", \"666\")] aaaaaaaahhhhh",
+            "HTTP/1.1 123 blub\r\nSpam-value: 666\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 13\r\n\r\naaaaaaaahhhhh",
             format!("{}", body)
         );
     }
@@ -113,17 +608,328 @@ mod test {
     #[test]
     fn send_with_body() {
         let httpresponse = HttpResponse::default();
+        let mut sent = Vec::new();
         httpresponse
-            .status_line(123, "blub")
-            .header("Length", "123")
+            .status(StatusCode::OK)
             .header("Spam-value", "666")
+            .unwrap()
             .body("aaaaaaaahhhhh")
-            .send();
+            .send(&mut sent)
+            .unwrap();
+        assert_eq!(
+            b"HTTP/1.1 200 OK\r\nSpam-value: 666\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 13\r\n\r\naaaaaaaahhhhh"
+                .to_vec(),
+            sent
+        );
     }
 
     #[test]
     fn send_with_status() {
         let httpresponse = HttpResponse::default();
-        httpresponse.status_line(123, "blub").send();
+        let mut sent = Vec::new();
+        httpresponse
+            .status_line(StatusCode::new(123).unwrap(), "blub")
+            .unwrap()
+            .send(&mut sent)
+            .unwrap();
+        assert_eq!(b"HTTP/1.1 123 blub\r\n\r\n".to_vec(), sent);
+    }
+
+    #[test]
+    fn send_with_empty_body() {
+        let httpresponse = HttpResponse::default();
+        let mut sent = Vec::new();
+        httpresponse
+            .status(StatusCode::OK)
+            .body("")
+            .send(&mut sent)
+            .unwrap();
+        assert_eq!(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 0\r\n\r\n".to_vec(),
+            sent
+        );
+    }
+
+    #[tokio::test]
+    async fn send_async_writes_same_bytes() {
+        let build = || HttpResponse::default().status(StatusCode::OK).body("blub");
+        let mut sent = Vec::new();
+        build().send(&mut sent).unwrap();
+        let mut sent_async = Vec::new();
+        build().send_async(&mut sent_async).await.unwrap();
+        assert_eq!(sent, sent_async);
+    }
+
+    #[test]
+    fn status_codes_above_255() {
+        let mut sent = Vec::new();
+        HttpResponse::default()
+            .status(StatusCode::NOT_FOUND)
+            .send(&mut sent)
+            .unwrap();
+        assert_eq!(b"HTTP/1.1 404 Not Found\r\n\r\n".to_vec(), sent);
+
+        let mut sent = Vec::new();
+        HttpResponse::default()
+            .status_line(StatusCode::INTERNAL_SERVER_ERROR, "Oops")
+            .unwrap()
+            .send(&mut sent)
+            .unwrap();
+        assert_eq!(b"HTTP/1.1 500 Oops\r\n\r\n".to_vec(), sent);
+    }
+
+    #[test]
+    fn status_without_standard_reason() {
+        let response = HttpResponse::default().status(StatusCode::new(299).unwrap());
+        assert_eq!("HTTP/1.1 299 \r\n\r\n", response.to_string());
+    }
+
+    #[test]
+    fn rejects_header_injection() {
+        let response = || HttpResponse::default().status(StatusCode::OK);
+        assert_eq!(
+            Some(InvalidHeader::Name("Spam value".to_string())),
+            response().header("Spam value", "666").err()
+        );
+        assert_eq!(
+            Some(InvalidHeader::Name("X-A:".to_string())),
+            response().header("X-A:", "1").err()
+        );
+        assert_eq!(
+            Some(InvalidHeader::Value("1\r\nSet-Cookie: a=b".to_string())),
+            response().header("X-A", "1\r\nSet-Cookie: a=b").err()
+        );
+        assert_eq!(
+            Some(InvalidHeader::Value(" padded".to_string())),
+            response().set_header("X-A", " padded").err()
+        );
+        assert!(response().header("X-A", "tab\tand spaces").is_ok());
+    }
+
+    #[test]
+    fn rejects_reason_phrase_injection() {
+        let status_line = |reason| HttpResponse::default().status_line(StatusCode::OK, reason);
+        assert_eq!(
+            Some(InvalidHeader::Reason("OK\r\nSet-Cookie: a=b".to_string())),
+            status_line("OK\r\nSet-Cookie: a=b").err()
+        );
+        assert_eq!(
+            Some(InvalidHeader::Reason("OK\n".to_string())),
+            status_line("OK\n").err()
+        );
+        assert!(status_line("Very\tOK").is_ok());
+    }
+
+    #[test]
+    fn case_insensitive_lookup_and_replacement() {
+        let response = HttpResponse::default()
+            .status(StatusCode::OK)
+            .header("Set-Cookie", "a=1")
+            .unwrap()
+            .header("X-Spam", "1")
+            .unwrap()
+            .header("set-cookie", "b=2")
+            .unwrap();
+        assert_eq!(Some("a=1"), response.get_header("SET-COOKIE"));
+        assert_eq!(None, response.get_header("Cookie"));
+
+        let response = response.set_header("SET-COOKIE", "c=3").unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nSet-Cookie: c=3\r\nX-Spam: 1\r\n\r\n",
+            response.to_string()
+        );
+    }
+
+    #[test]
+    fn typed_headers() {
+        let response = HttpResponse::default()
+            .status(StatusCode::OK)
+            .content_type("text/plain; charset=utf-8")
+            .unwrap()
+            .content_length(3)
+            .date(UNIX_EPOCH + Duration::from_secs(784111777))
+            .connection(Connection::Close)
+            .connection(Connection::KeepAlive);
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: 3\r\n\
+             Date: Sun, 06 Nov 1994 08:49:37 GMT\r\n\
+             Connection: keep-alive\r\n\r\n",
+            response.to_string()
+        );
+    }
+
+    #[test]
+    fn body_overrides_content_length() {
+        let response = HttpResponse::default()
+            .status(StatusCode::OK)
+            .header("content-length", "123")
+            .unwrap()
+            .body("blub");
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\ncontent-length: 4\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nblub",
+            response.to_string()
+        );
+    }
+
+    #[test]
+    fn http_dates() {
+        assert_eq!("Thu, 01 Jan 1970 00:00:00 GMT", http_date(UNIX_EPOCH));
+        let date = |secs| http_date(UNIX_EPOCH + Duration::from_secs(secs));
+        assert_eq!("Tue, 29 Feb 2000 23:59:59 GMT", date(951868799));
+        assert_eq!("Mon, 01 Mar 2100 12:00:00 GMT", date(4107585600));
+    }
+
+    #[test]
+    fn stream_chunked_body() {
+        let mut response = HttpResponse::default()
+            .status(StatusCode::OK)
+            .content_length(100)
+            .content_type("text/plain")
+            .unwrap()
+            .stream(Vec::new())
+            .unwrap();
+        response.write_all(b"Wiki").unwrap();
+        response.write_all(b"").unwrap();
+        write!(response, "pedia {}", 42).unwrap();
+        let sent = response.finish().unwrap().into_inner();
+        assert_eq!(
+            b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\r\n\
+              4\r\nWiki\r\n8\r\npedia 42\r\n0\r\n\r\n"
+                .to_vec(),
+            sent
+        );
+    }
+
+    #[test]
+    fn stream_with_trailers() {
+        let mut response = HttpResponse::default()
+            .status(StatusCode::OK)
+            .stream(Vec::new())
+            .unwrap();
+        response.write_all(&[0; 26]).unwrap();
+        response.trailer("Checksum", "1234").unwrap();
+        assert_eq!(
+            Some(InvalidHeader::Value("a\r\nb".to_string())),
+            response.trailer("Checksum", "a\r\nb").err()
+        );
+        let finished = response.finish().unwrap();
+        // everything was already written to the stream
+        let mut sent_again = Vec::new();
+        let mut expected = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n1a\r\n".to_vec();
+        expected.extend_from_slice(&[0; 26]);
+        expected.extend_from_slice(b"\r\n0\r\nChecksum: 1234\r\n\r\n");
+        assert_eq!(expected, finished._sending_state.writer);
+        finished.send(&mut sent_again).unwrap();
+        assert!(sent_again.is_empty());
+    }
+
+    #[test]
+    fn binary_body() {
+        let mut sent = Vec::new();
+        HttpResponse::default()
+            .status(StatusCode::OK)
+            .body(vec![0x1f, 0x8b, 0, 13, 10])
+            .send(&mut sent)
+            .unwrap();
+        let mut expected = b"HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\n\
+                             Content-Length: 5\r\n\r\n"
+            .to_vec();
+        expected.extend_from_slice(&[0x1f, 0x8b, 0, 13, 10]);
+        assert_eq!(expected, sent);
+    }
+
+    #[test]
+    fn explicit_content_type_is_kept() {
+        let response = HttpResponse::default()
+            .status(StatusCode::OK)
+            .content_type("application/gzip")
+            .unwrap()
+            .body(&b"gz"[..]);
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/gzip\r\nContent-Length: 2\r\n\r\ngz",
+            response.to_string()
+        );
+    }
+
+    #[test]
+    fn json_body() {
+        #[derive(Serialize)]
+        struct Sum {
+            x: usize,
+            y: usize,
+            z: usize,
+        }
+        let body = Json::new(&Sum { x: 1, y: 2, z: 3 }).unwrap();
+        let response = HttpResponse::default().status(StatusCode::OK).body(body);
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 19\r\n\r\n\
+             {\"x\":1,\"y\":2,\"z\":3}",
+            response.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn file_body_larger_than_a_chunk() {
+        let path = std::env::temp_dir().join(format!("type_state_html_{}.txt", std::process::id()));
+        let content: Vec<u8> = (0..BODY_CHUNK_SIZE * 2 + 7)
+            .map(|i| b'a' + (i % 26) as u8)
+            .collect();
+        std::fs::write(&path, &content).unwrap();
+        let mut expected = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: {}\r\n\r\n",
+            content.len()
+        )
+        .into_bytes();
+        expected.extend_from_slice(&content);
+
+        let mut sent = Vec::new();
+        HttpResponse::default()
+            .status(StatusCode::OK)
+            .body(FileBody::open(&path).unwrap())
+            .send(&mut sent)
+            .unwrap();
+        assert_eq!(expected, sent);
+
+        let response = HttpResponse::default()
+            .status(StatusCode::OK)
+            .body(FileBody::open(&path).unwrap());
+        // spawned to check that sending a file is Send
+        let sent_async = tokio::spawn(async move {
+            let mut sent_async = Vec::new();
+            response.send_async(&mut sent_async).await.unwrap();
+            sent_async
+        })
+        .await
+        .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(expected, sent_async);
+    }
+
+    #[tokio::test]
+    async fn truncated_file_body() {
+        let path = std::env::temp_dir().join(format!(
+            "type_state_html_truncated_{}.txt",
+            std::process::id()
+        ));
+        std::fs::write(&path, vec![b'a'; BODY_CHUNK_SIZE + 7]).unwrap();
+        let response = HttpResponse::default()
+            .status(StatusCode::OK)
+            .body(FileBody::open(&path).unwrap());
+        let async_response = HttpResponse::default()
+            .status(StatusCode::OK)
+            .body(FileBody::open(&path).unwrap());
+        std::fs::write(&path, "abc").unwrap();
+
+        let error = response.send(&mut Vec::new()).unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+        let error = async_response
+            .send_async(&mut Vec::new())
+            .await
+            .unwrap_err();
+        assert_eq!(io::ErrorKind::UnexpectedEof, error.kind());
+        std::fs::remove_file(&path).unwrap();
     }
 }
@@ -1,7 +1,74 @@
+mod async_mutex_ordering;
+mod body;
+mod derived_builder;
 mod html;
+mod lifecycle;
 mod lock_order;
+mod lockdep;
 mod mutex_ordering;
+mod request;
+mod response;
+mod server;
+pub mod session;
+mod status;
 
+pub use async_mutex_ordering::AsyncPriorityMutex;
+pub use async_mutex_ordering::AsyncPriorityRwLock;
+pub use body::FileBody;
+pub use body::Json;
+pub use body::ResponseBody;
+pub use html::Body;
+pub use html::Connection;
+pub use html::Finished;
+pub use html::Headers;
 pub use html::HttpResponse;
+pub use html::InvalidHeader;
+pub use html::ResponseState;
+pub use html::RoutableState;
+pub use html::SendingState;
+pub use html::Start;
+pub use html::Streaming;
+pub use lifecycle::Closed;
+pub use lifecycle::Connected;
+pub use lifecycle::HalfClosed;
+pub use lifecycle::ReadableState;
+pub use lifecycle::ShutdownStream;
+pub use lifecycle::Socket;
+pub use lifecycle::SocketState;
+pub use lifecycle::Unconnected;
+pub use lock_order::LockAfter;
+pub use lock_order::LockBefore;
+pub use lock_order::OrderedMutex;
+pub use lock_order::TakenLevel;
+pub use lock_order::Unlocked;
+pub use lockdep::CheckedMutex;
+pub use lockdep::CheckedMutexGuard;
+pub use mutex_ordering::HeldPriority;
+pub use mutex_ordering::LockSet;
+pub use mutex_ordering::NoLocksHeld;
+pub use mutex_ordering::PriorityCondvar;
 pub use mutex_ordering::PriorityMutex;
+pub use mutex_ordering::PriorityRwLock;
+pub use mutex_ordering::TakenLockPriority;
+pub use mutex_ordering::TakenLockSet;
 pub use mutex_ordering::use_priority;
+pub use request::HttpRequest;
+pub use request::Limits;
+pub use request::Method;
+pub use request::ParseError;
+pub use request::ParseState;
+pub use request::RequestBody;
+pub use request::RequestHeaders;
+pub use request::RequestLine;
+pub use request::RequestParser;
+pub use request::RequestTarget;
+pub use request::Step;
+pub use request::Version;
+pub use request::parse_request;
+pub use response::ParsedResponse;
+pub use response::parse_response;
+pub use server::Router;
+pub use server::ServerConfig;
+pub use server::serve;
+pub use status::InvalidStatusCode;
+pub use status::StatusCode;
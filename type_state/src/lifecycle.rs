@@ -0,0 +1,302 @@
+use std::io::{self, Read, Write};
+use std::net::{Shutdown, TcpStream};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+/// A stream whose directions can be shut down separately, like a TCP
+/// socket.
+pub trait ShutdownStream {
+    fn shutdown(&self, how: Shutdown) -> io::Result<()>;
+}
+
+impl ShutdownStream for TcpStream {
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        TcpStream::shutdown(self, how)
+    }
+}
+
+#[cfg(unix)]
+impl ShutdownStream for std::os::unix::net::UnixStream {
+    fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        std::os::unix::net::UnixStream::shutdown(self, how)
+    }
+}
+
+/// Not connected yet, `C` opens the connection.
+pub struct Unconnected<C> {
+    connector: C,
+}
+
+/// Both directions are open.
+pub struct Connected<T> {
+    stream: T,
+}
+
+/// Our direction is shut down, the peer may still send.
+pub struct HalfClosed<T> {
+    stream: T,
+}
+
+/// The stream is shut down and dropped.
+pub struct Closed;
+
+pub trait SocketState {}
+
+impl<C> SocketState for Unconnected<C> {}
+impl<T> SocketState for Connected<T> {}
+impl<T> SocketState for HalfClosed<T> {}
+impl SocketState for Closed {}
+
+/// States in which data can still be received.
+pub trait ReadableState: SocketState {
+    type Stream;
+
+    fn stream(&mut self) -> &mut Self::Stream;
+}
+
+impl<T> ReadableState for Connected<T> {
+    type Stream = T;
+
+    fn stream(&mut self) -> &mut T {
+        &mut self.stream
+    }
+}
+
+impl<T> ReadableState for HalfClosed<T> {
+    type Stream = T;
+
+    fn stream(&mut self) -> &mut T {
+        &mut self.stream
+    }
+}
+
+/// The lifecycle of a connection, `read` and `write` only exist in the
+/// states in which they are allowed:
+///
+/// ```compile_fail
+/// use std::io::Write;
+/// use std::net::{TcpListener, TcpStream};
+///
+/// use type_state::Socket;
+///
+/// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+/// let addr = listener.local_addr().unwrap();
+/// let socket = Socket::new(|| TcpStream::connect(addr)).connect().unwrap();
+/// let mut socket = socket.shutdown_write().unwrap();
+/// socket.write(b"too late").unwrap();
+/// ```
+pub struct Socket<S: SocketState> {
+    state: S,
+}
+
+impl<C> Socket<Unconnected<C>> {
+    pub fn new(connector: C) -> Self {
+        Socket {
+            state: Unconnected { connector },
+        }
+    }
+
+    pub fn connect<T: Read + Write>(self) -> io::Result<Socket<Connected<T>>>
+    where
+        C: FnOnce() -> io::Result<T>,
+    {
+        Ok(Socket::from_stream((self.state.connector)()?))
+    }
+
+    pub async fn connect_async<T, F>(self) -> io::Result<Socket<Connected<T>>>
+    where
+        C: FnOnce() -> F,
+        F: Future<Output = io::Result<T>>,
+        T: AsyncRead + AsyncWrite + Unpin,
+    {
+        Ok(Socket::from_stream((self.state.connector)().await?))
+    }
+}
+
+impl<T> Socket<Connected<T>> {
+    /// Wraps a stream that is already connected, e.g. an accepted one.
+    pub fn from_stream(stream: T) -> Self {
+        Socket {
+            state: Connected { stream },
+        }
+    }
+
+    pub fn write(&mut self, buf: &[u8]) -> io::Result<usize>
+    where
+        T: Write,
+    {
+        self.state.stream.write(buf)
+    }
+
+    pub fn write_all(&mut self, buf: &[u8]) -> io::Result<()>
+    where
+        T: Write,
+    {
+        self.state.stream.write_all(buf)
+    }
+
+    pub async fn write_async(&mut self, buf: &[u8]) -> io::Result<usize>
+    where
+        T: AsyncWrite + Unpin,
+    {
+        self.state.stream.write(buf).await
+    }
+
+    pub async fn write_all_async(&mut self, buf: &[u8]) -> io::Result<()>
+    where
+        T: AsyncWrite + Unpin,
+    {
+        self.state.stream.write_all(buf).await
+    }
+
+    /// Flushes and shuts down the sending direction, the peer reads the end
+    /// of the stream.
+    pub fn shutdown_write(mut self) -> io::Result<Socket<HalfClosed<T>>>
+    where
+        T: Write + ShutdownStream,
+    {
+        self.state.stream.flush()?;
+        self.state.stream.shutdown(Shutdown::Write)?;
+        Ok(Socket {
+            state: HalfClosed {
+                stream: self.state.stream,
+            },
+        })
+    }
+
+    pub async fn shutdown_write_async(mut self) -> io::Result<Socket<HalfClosed<T>>>
+    where
+        T: AsyncWrite + Unpin,
+    {
+        self.state.stream.shutdown().await?;
+        Ok(Socket {
+            state: HalfClosed {
+                stream: self.state.stream,
+            },
+        })
+    }
+
+    pub fn close(self) -> io::Result<Socket<Closed>>
+    where
+        T: Write + ShutdownStream,
+    {
+        self.shutdown_write()?.close()
+    }
+
+    pub async fn close_async(self) -> io::Result<Socket<Closed>>
+    where
+        T: AsyncWrite + Unpin,
+    {
+        self.shutdown_write_async().await?.close_async().await
+    }
+}
+
+impl<T> Socket<HalfClosed<T>> {
+    pub fn close(self) -> io::Result<Socket<Closed>>
+    where
+        T: ShutdownStream,
+    {
+        // the peer may have closed already
+        match self.state.stream.shutdown(Shutdown::Read) {
+            Err(e) if e.kind() != io::ErrorKind::NotConnected => Err(e),
+            _ => Ok(Socket { state: Closed }),
+        }
+    }
+
+    pub async fn close_async(self) -> io::Result<Socket<Closed>> {
+        // tokio streams have no read shutdown, dropping them closes both
+        drop(self.state.stream);
+        Ok(Socket { state: Closed })
+    }
+}
+
+impl<S: ReadableState> Socket<S> {
+    /// Reads into `buf`, 0 means the peer shut down its sending direction.
+    pub fn read(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    where
+        S::Stream: Read,
+    {
+        self.state.stream().read(buf)
+    }
+
+    pub fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize>
+    where
+        S::Stream: Read,
+    {
+        self.state.stream().read_to_end(buf)
+    }
+
+    pub async fn read_async(&mut self, buf: &mut [u8]) -> io::Result<usize>
+    where
+        S::Stream: AsyncRead + Unpin,
+    {
+        self.state.stream().read(buf).await
+    }
+
+    pub async fn read_to_end_async(&mut self, buf: &mut Vec<u8>) -> io::Result<usize>
+    where
+        S::Stream: AsyncRead + Unpin,
+    {
+        self.state.stream().read_to_end(buf).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::{TcpListener, TcpStream};
+    use std::thread;
+
+    use super::{Closed, Socket};
+
+    #[test]
+    fn loopback_through_every_state() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = Socket::from_stream(stream);
+            let mut request = Vec::new();
+            socket.read_to_end(&mut request).unwrap();
+            socket.write_all(b"pong").unwrap();
+            socket.close().unwrap();
+            request
+        });
+
+        let socket = Socket::new(|| TcpStream::connect(addr));
+        let mut socket = socket.connect().unwrap();
+        socket.write_all(b"ping").unwrap();
+        let mut socket = socket.shutdown_write().unwrap();
+        let mut response = Vec::new();
+        socket.read_to_end(&mut response).unwrap();
+        let Socket { state: Closed } = socket.close().unwrap();
+
+        assert_eq!(b"pong", &response[..]);
+        assert_eq!(b"ping", &server.join().unwrap()[..]);
+    }
+
+    #[tokio::test]
+    async fn async_loopback_through_every_state() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = Socket::from_stream(stream);
+            let mut request = Vec::new();
+            socket.read_to_end_async(&mut request).await.unwrap();
+            socket.write_all_async(b"pong").await.unwrap();
+            socket.close_async().await.unwrap();
+            request
+        });
+
+        let socket = Socket::new(|| tokio::net::TcpStream::connect(addr));
+        let mut socket = socket.connect_async().await.unwrap();
+        socket.write_all_async(b"ping").await.unwrap();
+        let mut socket = socket.shutdown_write_async().await.unwrap();
+        let mut response = Vec::new();
+        socket.read_to_end_async(&mut response).await.unwrap();
+        socket.close_async().await.unwrap();
+
+        assert_eq!(b"pong", &response[..]);
+        assert_eq!(b"ping", &server.await.unwrap()[..]);
+    }
+}
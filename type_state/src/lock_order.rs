@@ -1,10 +1,138 @@
+use std::marker::PhantomData;
+use std::sync::{LockResult, Mutex, MutexGuard, PoisonError};
+
+// Based on https://cs.opensource.google/fuchsia/fuchsia/+/main:src/connectivity/network/netstack3/core/lock-order/src/relation.rs;l=107;drc=e2e00bc897e7362f33b25a7d98d9d7ba5ff07f69
+
+/// `Self` may be locked while `A` is held, forward in the lock graph.
+pub trait LockAfter<A> {}
+
+/// `Self` may be held while `X` is locked, backward in the lock graph.
+pub trait LockBefore<X> {}
+
+// automatic backward implementation
+impl<B: LockAfter<A>, A> LockBefore<B> for A {}
+
+/// The level of a thread that holds no lock of a hierarchy.
+pub enum Unlocked {}
+
+/// Declares a lock hierarchy: one level type per name, with the levels
+/// right of `=>` locked after the one on the left, transitively. Every
+/// level except the first one must appear exactly once on the right side of
+/// `=>`, so the hierarchy is a tree. Locks are created as
+/// `OrderedMutex<Level, T>`.
+///
+/// ```
+/// use type_state::{OrderedMutex, TakenLevel, lock_hierarchy};
+///
+/// lock_hierarchy! {
+///     Config => Connections => Buffers;
+///     Config => Metrics
+/// }
+///
+/// let config = OrderedMutex::<Config, _>::new("config");
+/// let buffers = OrderedMutex::<Buffers, _>::new(Vec::<u8>::new());
+///
+/// let mut root = TakenLevel::root();
+/// let (mut at_config, _config) = config.lock(&mut root).unwrap();
+/// let (_, mut buffers) = buffers.lock(&mut at_config).unwrap();
+/// buffers.push(1);
+/// ```
+///
+/// A lock that is declared earlier can't be taken later:
+///
+/// ```compile_fail
+/// use type_state::{OrderedMutex, TakenLevel, lock_hierarchy};
+///
+/// lock_hierarchy! { A => B => C; A => D }
+///
+/// let b = OrderedMutex::<B, _>::new(());
+/// let d = OrderedMutex::<D, _>::new(());
+/// let mut root = TakenLevel::root();
+/// let (mut at_b, _b) = b.lock(&mut root).unwrap();
+/// let (_at_d, _d) = d.lock(&mut at_b).unwrap();
+/// ```
+///
+/// and a cycle in the hierarchy fails to compile. Closing the cycle declares
+/// its first level a second time, so the first error is E0428 (`A` is
+/// defined multiple times), followed by conflicting implementations of
+/// `LockAfter` (E0119):
+///
+/// ```compile_fail
+/// use type_state::lock_hierarchy;
+///
+/// lock_hierarchy! { A => B => C => D => A }
+/// ```
+#[macro_export]
+macro_rules! lock_hierarchy {
+    (@chains) => {};
+    (@chains $head:ident $(=> $level:ident)+ $(; $($rest:tt)*)?) => {
+        $crate::lock_hierarchy!(@chain $head $(=> $level)+);
+        $($crate::lock_hierarchy!(@chains $($rest)*);)?
+    };
+    (@chain $before:ident) => {};
+    (@chain $before:ident => $after:ident $(=> $level:ident)*) => {
+        pub enum $after {}
+        impl $crate::LockAfter<$before> for $after {}
+        // a circular dependency declares a level twice, which already fails,
+        // and this creates a second implementation of LockAfter<$before>
+        impl<X: $crate::LockBefore<$before>> $crate::LockAfter<X> for $after {}
+        $crate::lock_hierarchy!(@chain $after $(=> $level)*);
+    };
+    ($root:ident $(=> $level:ident)* $(; $($rest:tt)*)?) => {
+        pub enum $root {}
+        impl $crate::LockAfter<$crate::Unlocked> for $root {}
+        $crate::lock_hierarchy!(@chain $root $(=> $level)*);
+        $($crate::lock_hierarchy!(@chains $($rest)*);)?
+    };
+}
+
+/// Proof that a lock of level `L` is held, needed to lock the next level.
+pub struct TakenLevel<'a, L> {
+    level: PhantomData<&'a mut L>,
+}
+
+impl TakenLevel<'static, Unlocked> {
+    pub fn root() -> Self {
+        TakenLevel { level: PhantomData }
+    }
+}
+
+/// A mutex at level `L` of a hierarchy declared with `lock_hierarchy!`.
+pub struct OrderedMutex<L, T: ?Sized> {
+    level: PhantomData<L>,
+    mutex: Mutex<T>,
+}
+
+impl<L, T> OrderedMutex<L, T> {
+    pub const fn new(value: T) -> Self {
+        OrderedMutex {
+            level: PhantomData,
+            mutex: Mutex::new(value),
+        }
+    }
+}
+
+impl<L, T: ?Sized> OrderedMutex<L, T> {
+    pub fn lock<'c, P: LockBefore<L>>(
+        &self,
+        _taken: &'c mut TakenLevel<'_, P>,
+    ) -> LockResult<(TakenLevel<'c, L>, MutexGuard<'_, T>)> {
+        let taken = |guard| (TakenLevel { level: PhantomData }, guard);
+        self.mutex
+            .lock()
+            .map(taken)
+            .map_err(|poisoned| PoisonError::new(taken(poisoned.into_inner())))
+    }
+}
+
 #[cfg(test)]
 mod test {
-
     use lock_ordering::{
         LockLevel, LockedAt, MutualExclusion, Unlocked, lock::MutexLockLevel, relation::LockAfter,
     };
 
+    use super::{OrderedMutex, TakenLevel};
+
     struct FirstLock;
     struct SecondLock;
     struct ThirdLock;
@@ -69,44 +197,32 @@ mod test {
         *first_guard = 666;
     }
 
-    // from https://cs.opensource.google/fuchsia/fuchsia/+/main:src/connectivity/network/netstack3/core/lock-order/src/relation.rs;l=107;drc=e2e00bc897e7362f33b25a7d98d9d7ba5ff07f69
-    // dead code warnings are suppressed, because these types are only used
-    // by the borrow checker and will never land in a binary. Maybe the warning
-    // also would go away if the full test of fuchsia would be copied
-    // forward in graph
-    #[allow(dead_code)]
-    pub trait LockAfterF<A> {}
-
-    // backward in graph
-    #[allow(dead_code)]
-    pub trait LockBefore<X> {}
-
-    // automatic backward implementation
-    impl<B: LockAfterF<A>, A> LockBefore<B> for A {}
-
-    // creates a graph with edges from A to B and all predecessors of A to B
-    #[macro_export]
-    macro_rules! impl_lock_after {
-        ($A:ty => $B:ty) => {
-            impl LockAfterF<$A> for $B {}
-            // in case of circular dependency this will create a second trait LockAfterF<$A>
-            // implementation and cause compile error
-            impl<X: LockBefore<$A>> LockAfterF<X> for $B {}
-        };
+    lock_hierarchy! {
+        A => B => C;
+        A => D
     }
 
-    #[allow(dead_code)]
-    enum A {}
-    #[allow(dead_code)]
-    enum B {}
-    #[allow(dead_code)]
-    enum C {}
-    #[allow(dead_code)]
-    enum D {}
-
-    impl_lock_after!(A => B);
-    // impl_lock_after!(B => A);
-    impl_lock_after!(B => C);
-    impl_lock_after!(C => D);
-    // impl_lock_after!(D => A); // this will create a compile error
+    #[test]
+    fn hierarchy() {
+        let a = OrderedMutex::<A, _>::new(1);
+        let b = OrderedMutex::<B, _>::new(2);
+        let c = OrderedMutex::<C, _>::new(3);
+        let d = OrderedMutex::<D, _>::new(4);
+
+        let mut root = TakenLevel::root();
+        {
+            let (mut at_a, a) = a.lock(&mut root).unwrap();
+            // levels may be skipped
+            let (_, c) = c.lock(&mut at_a).unwrap();
+            assert_eq!(4, *a + *c);
+        }
+        {
+            let (mut at_b, _b) = b.lock(&mut root).unwrap();
+            let (_, mut c) = c.lock(&mut at_b).unwrap();
+            *c += 1;
+        }
+        let (_, d) = d.lock(&mut root).unwrap();
+        assert_eq!(4, *d);
+        assert_eq!(4, *c.lock(&mut TakenLevel::root()).unwrap().1);
+    }
 }
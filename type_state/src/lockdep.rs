@@ -0,0 +1,368 @@
+use std::ops::{Deref, DerefMut};
+use std::sync::{LockResult, Mutex, MutexGuard, PoisonError};
+
+#[cfg(debug_assertions)]
+use std::backtrace::Backtrace;
+#[cfg(debug_assertions)]
+use std::cell::RefCell;
+#[cfg(debug_assertions)]
+use std::collections::HashMap;
+#[cfg(debug_assertions)]
+use std::fmt;
+#[cfg(debug_assertions)]
+use std::sync::Arc;
+#[cfg(debug_assertions)]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(debug_assertions)]
+enum LockOrderViolation {
+    // A lock order that contradicts one seen earlier, i.e. two threads could
+    // deadlock by taking the locks in `cycle`. The cycle starts and ends with
+    // the lock that was about to be acquired, `previous` is where its first
+    // edge was recorded.
+    Cycle {
+        cycle: Vec<&'static str>,
+        previous: Arc<Backtrace>,
+        current: Arc<Backtrace>,
+    },
+    // the thread already holds the lock, so it would wait for itself
+    Recursive {
+        name: &'static str,
+        current: Arc<Backtrace>,
+    },
+}
+
+#[cfg(debug_assertions)]
+impl fmt::Display for LockOrderViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockOrderViolation::Cycle {
+                cycle,
+                previous,
+                current,
+            } => {
+                writeln!(f, "possible deadlock: {}", cycle.join(" -> "))?;
+                writeln!(f, "acquired in the opposite order at:\n{previous}")?;
+                write!(f, "now acquired at:\n{current}")
+            }
+            LockOrderViolation::Recursive { name, current } => {
+                writeln!(f, "deadlock: {name} is already held by this thread")?;
+                write!(f, "acquired again at:\n{current}")
+            }
+        }
+    }
+}
+
+#[cfg(debug_assertions)]
+struct Held {
+    id: usize,
+}
+
+// locks acquired after `from` while it was held, with where that happened
+#[cfg(debug_assertions)]
+#[derive(Default)]
+struct Graph {
+    names: HashMap<usize, &'static str>,
+    edges: HashMap<usize, HashMap<usize, Arc<Backtrace>>>,
+}
+
+#[cfg(debug_assertions)]
+impl Graph {
+    // depth first search, returns the nodes from `from` to `to`
+    fn path(&self, from: usize, to: usize) -> Option<Vec<usize>> {
+        let mut stack = vec![vec![from]];
+        let mut seen = vec![from];
+        while let Some(path) = stack.pop() {
+            let last = path[path.len() - 1];
+            if last == to {
+                return Some(path);
+            }
+            for &next in self
+                .edges
+                .get(&last)
+                .into_iter()
+                .flat_map(|next| next.keys())
+            {
+                if !seen.contains(&next) {
+                    seen.push(next);
+                    let mut path = path.clone();
+                    path.push(next);
+                    stack.push(path);
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(debug_assertions)]
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(debug_assertions)]
+static GRAPH: Mutex<Option<Graph>> = Mutex::new(None);
+
+#[cfg(debug_assertions)]
+thread_local! {
+    static HELD: RefCell<Vec<Held>> = const { RefCell::new(Vec::new()) };
+}
+
+// records the new order and reports whether it closes a cycle
+#[cfg(debug_assertions)]
+fn acquire(id: usize, name: &'static str) -> Result<(), LockOrderViolation> {
+    // capturing is slow, so it only happens for a new edge or a report
+    let mut current = None;
+    let mut capture = || {
+        current
+            .get_or_insert_with(|| Arc::new(Backtrace::force_capture()))
+            .clone()
+    };
+    let mut graph = GRAPH.lock().unwrap_or_else(PoisonError::into_inner);
+    let graph = graph.get_or_insert_with(Graph::default);
+    graph.names.insert(id, name);
+    HELD.with_borrow_mut(|held| {
+        for lock in held.iter() {
+            if lock.id == id {
+                return Err(LockOrderViolation::Recursive {
+                    name,
+                    current: capture(),
+                });
+            }
+            let known = graph
+                .edges
+                .get(&lock.id)
+                .is_some_and(|next| next.contains_key(&id));
+            if known {
+                continue;
+            }
+            if let Some(path) = graph.path(id, lock.id) {
+                let previous = graph.edges[&path[0]][&path[1]].clone();
+                let mut cycle: Vec<_> = path.iter().map(|id| graph.names[id]).collect();
+                cycle.push(name);
+                return Err(LockOrderViolation::Cycle {
+                    cycle,
+                    previous,
+                    current: capture(),
+                });
+            }
+            graph
+                .edges
+                .entry(lock.id)
+                .or_default()
+                .insert(id, capture());
+        }
+        held.push(Held { id });
+        Ok(())
+    })
+}
+
+#[cfg(debug_assertions)]
+fn release(id: usize) {
+    HELD.with_borrow_mut(|held| {
+        if let Some(position) = held.iter().rposition(|lock| lock.id == id) {
+            held.remove(position);
+        }
+    });
+}
+
+/// A mutex that, in debug builds, records in which order locks are taken
+/// across all threads and panics as soon as two orders contradict each other,
+/// even if no deadlock happened yet. The panic message names the locks of the
+/// cycle and has the backtraces of both orders. Release builds only lock the
+/// mutex.
+pub struct CheckedMutex<T: ?Sized> {
+    #[cfg(debug_assertions)]
+    id: usize,
+    name: &'static str,
+    mutex: Mutex<T>,
+}
+
+impl<T> CheckedMutex<T> {
+    pub fn new(name: &'static str, value: T) -> Self {
+        CheckedMutex {
+            #[cfg(debug_assertions)]
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            name,
+            mutex: Mutex::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> CheckedMutex<T> {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Locks the mutex, the order check runs before blocking so the report
+    /// is also made for the lock that would deadlock.
+    pub fn lock(&self) -> LockResult<CheckedMutexGuard<'_, T>> {
+        #[cfg(debug_assertions)]
+        if let Err(violation) = acquire(self.id, self.name) {
+            panic!("{violation}");
+        }
+        let checked = |guard| CheckedMutexGuard {
+            #[cfg(debug_assertions)]
+            id: self.id,
+            guard,
+        };
+        self.mutex
+            .lock()
+            .map(checked)
+            .map_err(|poisoned| PoisonError::new(checked(poisoned.into_inner())))
+    }
+}
+
+impl<T: ?Sized> Drop for CheckedMutex<T> {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        if let Some(graph) = GRAPH
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_mut()
+        {
+            graph.names.remove(&self.id);
+            graph.edges.remove(&self.id);
+            for next in graph.edges.values_mut() {
+                next.remove(&self.id);
+            }
+        }
+    }
+}
+
+pub struct CheckedMutexGuard<'a, T: ?Sized> {
+    #[cfg(debug_assertions)]
+    id: usize,
+    guard: MutexGuard<'a, T>,
+}
+
+impl<T: ?Sized> Deref for CheckedMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T: ?Sized> DerefMut for CheckedMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T: ?Sized> Drop for CheckedMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        #[cfg(debug_assertions)]
+        release(self.id);
+    }
+}
+
+#[cfg(all(test, debug_assertions))]
+mod test {
+    use std::thread;
+
+    use super::CheckedMutex;
+
+    // the panic message of a lock order violation
+    fn report(result: thread::Result<()>) -> String {
+        *result
+            .expect_err("no lock order violation reported")
+            .downcast()
+            .unwrap()
+    }
+
+    #[test]
+    fn abba() {
+        let a = CheckedMutex::new("a", 0);
+        let b = CheckedMutex::new("b", 0);
+        thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _a = a.lock().unwrap();
+                    let _b = b.lock().unwrap();
+                })
+                .join()
+                .unwrap();
+            let report = report(
+                scope
+                    .spawn(|| {
+                        let _b = b.lock().unwrap();
+                        let _a = a.lock().unwrap();
+                    })
+                    .join(),
+            );
+            assert!(report.starts_with("possible deadlock: a -> b -> a\n"));
+            // both backtraces were captured
+            let (previous, current) = report.split_once("now acquired at:\n").unwrap();
+            assert!(previous.contains("acquired in the opposite order at:\n"));
+            assert!(previous.contains("lockdep::acquire"));
+            assert!(current.contains("lockdep::acquire"));
+        });
+        // the report unwound while b was held, so b is released but poisoned
+        assert!(b.lock().is_err());
+    }
+
+    #[test]
+    fn transitive_cycle() {
+        let a = CheckedMutex::new("a", ());
+        let b = CheckedMutex::new("b", ());
+        let c = CheckedMutex::new("c", ());
+        {
+            let _a = a.lock().unwrap();
+            let _b = b.lock().unwrap();
+        }
+        {
+            let _b = b.lock().unwrap();
+            let _c = c.lock().unwrap();
+        }
+        let report = report(thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _c = c.lock().unwrap();
+                    let _a = a.lock().unwrap();
+                })
+                .join()
+        }));
+        assert!(report.starts_with("possible deadlock: a -> b -> c -> a\n"));
+    }
+
+    #[test]
+    fn recursive_lock() {
+        let a = CheckedMutex::new("a", ());
+        let report = report(thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _a = a.lock().unwrap();
+                    let _again = a.lock().unwrap();
+                })
+                .join()
+        }));
+        assert!(report.starts_with("deadlock: a is already held by this thread\n"));
+        let (_, current) = report.split_once("acquired again at:\n").unwrap();
+        assert!(current.contains("lockdep::acquire"));
+        // the first guard was released while unwinding, but poisoned a
+        assert!(a.lock().is_err());
+    }
+
+    #[test]
+    fn consistent_order() {
+        let a = CheckedMutex::new("a", 1);
+        let b = CheckedMutex::new("b", 2);
+        thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        let mut a = a.lock().unwrap();
+                        let b = b.lock().unwrap();
+                        *a += *b;
+                    }
+                    // released in the same order they were taken
+                    let first = a.lock().unwrap();
+                    let second = b.lock().unwrap();
+                    drop(first);
+                    drop(second);
+                    let _b = b.lock().unwrap();
+                });
+            }
+        });
+        assert_eq!(81, *a.lock().unwrap());
+    }
+}
@@ -1,65 +1,632 @@
 use std::marker::PhantomData;
-use std::sync::Mutex;
 use std::sync::MutexGuard;
+use std::sync::{Condvar, Mutex, WaitTimeoutResult};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread;
+use std::time::{Duration, Instant};
 
 pub struct TakenLockPriority<'a, T: ?Sized, const PRIORITY: usize> {
-    phantom: PhantomData<&'a mut T>,
+    pub(crate) phantom: PhantomData<&'a mut T>,
 }
 
+/// Token for a thread that holds no priority lock yet, every lock can be
+/// taken with it.
+pub type NoLocksHeld = TakenLockPriority<'static, (), 0>;
+
+impl NoLocksHeld {
+    pub fn root() -> NoLocksHeld {
+        TakenLockPriority {
+            phantom: PhantomData,
+        }
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A token that proves locks up to `PRIORITY` are held. Sealed, only the
+/// tokens handed out by the locks of this crate implement it.
+pub trait HeldPriority: sealed::Sealed {
+    const PRIORITY: usize;
+}
+
+impl<T: ?Sized, const PRIORITY: usize> sealed::Sealed for TakenLockPriority<'_, T, PRIORITY> {}
+
+impl<T: ?Sized, const PRIORITY: usize> HeldPriority for TakenLockPriority<'_, T, PRIORITY> {
+    const PRIORITY: usize = PRIORITY;
+}
+
+// evaluated at compile time for every lock call
+pub(crate) const fn check_order(previous_priority: usize, priority: usize) {
+    if previous_priority >= priority {
+        panic!("Improper use of lock is detetected")
+    }
+}
+
+/// A mutex that can only be locked while holding the token of a lock with a
+/// lower priority.
+///
+/// ```compile_fail
+/// use type_state::{NoLocksHeld, PriorityMutex, use_priority};
+///
+/// let mut root = NoLocksHeld::root();
+/// let m1 = PriorityMutex::<'_, (), (), 1>::new(());
+/// let m2 = PriorityMutex::<'_, (), (), 2>::new(());
+/// let (mut token2, _guard2) = m2.lock(use_priority(&mut root)).unwrap();
+/// let (_token1, _guard1) = m1.lock(use_priority(&mut token2)).unwrap();
+/// ```
 pub struct PriorityMutex<'a, T: ?Sized, U, const PRIORITY: usize> {
     previous: PhantomData<&'a mut U>,
     mutex: Mutex<T>,
 }
 
+impl<T, U, const PRIORITY: usize> PriorityMutex<'_, T, U, PRIORITY> {
+    pub const fn new(value: T) -> Self {
+        PriorityMutex {
+            previous: PhantomData,
+            mutex: Mutex::new(value),
+        }
+    }
+}
+
 impl<T: ?Sized, U, const PRIORITY: usize> PriorityMutex<'_, T, U, PRIORITY> {
-    pub fn lock<'c, V, const PREVIOUS_PRIORITY: usize>(
+    /// Blocks until the mutex is acquired. If another holder panicked the
+    /// error still carries the token and guard, see `PoisonError::into_inner`.
+    pub fn lock<'c, H: HeldPriority>(
+        &self,
+        _previous_priority: PhantomData<&'c mut H>,
+    ) -> LockResult<(TakenLockPriority<'c, Self, PRIORITY>, MutexGuard<'_, T>)> {
+        const { check_order(H::PRIORITY, PRIORITY) }
+        with_token(self.mutex.lock())
+    }
+
+    /// Acquires the mutex only if nobody holds it, no token is handed out on
+    /// `WouldBlock`.
+    pub fn try_lock<'c, H: HeldPriority>(
+        &self,
+        _previous_priority: PhantomData<&'c mut H>,
+    ) -> TryLockResult<(TakenLockPriority<'c, Self, PRIORITY>, MutexGuard<'_, T>)> {
+        const { check_order(H::PRIORITY, PRIORITY) }
+        try_with_token(self.mutex.try_lock())
+    }
+
+    /// Like `try_lock`, but keeps retrying until `timeout` has passed.
+    /// `WouldBlock` means the timeout expired.
+    pub fn lock_timeout<'c, H: HeldPriority>(
         &self,
-        _previous_priority: PhantomData<&'c mut TakenLockPriority<'_, V, PREVIOUS_PRIORITY>>,
-    ) -> (TakenLockPriority<'c, Self, PRIORITY>, MutexGuard<'_, T>) {
-        const {
-            if PREVIOUS_PRIORITY >= PRIORITY {
-                panic!("Improper use of lock is detetected")
+        previous_priority: PhantomData<&'c mut H>,
+        timeout: Duration,
+    ) -> TryLockResult<(TakenLockPriority<'c, Self, PRIORITY>, MutexGuard<'_, T>)> {
+        let deadline = Instant::now() + timeout;
+        let mut backoff = Duration::from_micros(10);
+        loop {
+            match self.try_lock(previous_priority) {
+                Err(TryLockError::WouldBlock) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(TryLockError::WouldBlock);
+                    }
+                    thread::sleep(backoff.min(remaining));
+                    backoff = (backoff * 2).min(Duration::from_millis(1));
+                }
+                result => return result,
             }
         }
+    }
+}
+
+fn with_token<'c, T: ?Sized, const PRIORITY: usize, G>(
+    result: LockResult<G>,
+) -> LockResult<(TakenLockPriority<'c, T, PRIORITY>, G)> {
+    let taken = |guard| {
         (
-            TakenLockPriority::<'_, _, PRIORITY> {
-                phantom: PhantomData::<&mut _>,
+            TakenLockPriority {
+                phantom: PhantomData,
             },
-            self.mutex.lock().unwrap(),
+            guard,
         )
+    };
+    result
+        .map(taken)
+        .map_err(|poisoned| PoisonError::new(taken(poisoned.into_inner())))
+}
+
+fn try_with_token<'c, T: ?Sized, const PRIORITY: usize, G>(
+    result: TryLockResult<G>,
+) -> TryLockResult<(TakenLockPriority<'c, T, PRIORITY>, G)> {
+    match result {
+        Ok(guard) => with_token(Ok(guard)).map_err(TryLockError::Poisoned),
+        Err(TryLockError::Poisoned(poisoned)) => {
+            with_token(Err(poisoned)).map_err(TryLockError::Poisoned)
+        }
+        Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+    }
+}
+
+/// The reader-writer flavour of `PriorityMutex`, both read and write locks
+/// follow the same ordering.
+///
+/// ```compile_fail
+/// use type_state::{NoLocksHeld, PriorityRwLock, use_priority};
+///
+/// let mut root = NoLocksHeld::root();
+/// let l1 = PriorityRwLock::<'_, (), (), 1>::new(());
+/// let l2 = PriorityRwLock::<'_, (), (), 2>::new(());
+/// let (mut token2, _guard2) = l2.read(use_priority(&mut root)).unwrap();
+/// let (_token1, _guard1) = l1.write(use_priority(&mut token2)).unwrap();
+/// ```
+pub struct PriorityRwLock<'a, T: ?Sized, U, const PRIORITY: usize> {
+    previous: PhantomData<&'a mut U>,
+    lock: RwLock<T>,
+}
+
+impl<T, U, const PRIORITY: usize> PriorityRwLock<'_, T, U, PRIORITY> {
+    pub const fn new(value: T) -> Self {
+        PriorityRwLock {
+            previous: PhantomData,
+            lock: RwLock::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized, U, const PRIORITY: usize> PriorityRwLock<'_, T, U, PRIORITY> {
+    pub fn read<'c, H: HeldPriority>(
+        &self,
+        _previous_priority: PhantomData<&'c mut H>,
+    ) -> LockResult<(
+        TakenLockPriority<'c, Self, PRIORITY>,
+        RwLockReadGuard<'_, T>,
+    )> {
+        const { check_order(H::PRIORITY, PRIORITY) }
+        with_token(self.lock.read())
+    }
+
+    pub fn write<'c, H: HeldPriority>(
+        &self,
+        _previous_priority: PhantomData<&'c mut H>,
+    ) -> LockResult<(
+        TakenLockPriority<'c, Self, PRIORITY>,
+        RwLockWriteGuard<'_, T>,
+    )> {
+        const { check_order(H::PRIORITY, PRIORITY) }
+        with_token(self.lock.write())
+    }
+}
+
+// the order in which to lock mutexes with these priorities
+const fn lock_set_order<const N: usize>(
+    priorities: [usize; N],
+    previous_priority: usize,
+) -> [usize; N] {
+    let mut order = [0; N];
+    let mut i = 0;
+    while i < N {
+        order[i] = i;
+        i += 1;
+    }
+    // insertion sort, `sort` is not const
+    let mut i = 1;
+    while i < N {
+        let mut j = i;
+        while j > 0 && priorities[order[j - 1]] > priorities[order[j]] {
+            let swap = order[j - 1];
+            order[j - 1] = order[j];
+            order[j] = swap;
+            j -= 1;
+        }
+        i += 1;
+    }
+    let mut i = 1;
+    while i < N {
+        if priorities[order[i - 1]] == priorities[order[i]] {
+            panic!("Two mutexes of a lock set have the same priority")
+        }
+        i += 1;
+    }
+    check_order(previous_priority, priorities[order[0]]);
+    order
+}
+
+/// Several `PriorityMutex`es that are locked together, see `lock_all!`.
+pub trait LockSet {
+    /// The highest priority in the set.
+    const PRIORITY: usize;
+
+    type Guards;
+
+    fn lock_all<'c, H: HeldPriority>(
+        self,
+        previous_priority: PhantomData<&'c mut H>,
+    ) -> LockResult<(Self::Guards, TakenLockSet<'c, Self>)>
+    where
+        Self: Sized;
+}
+
+/// Token for a locked `LockSet`, it has the highest priority of the set.
+pub struct TakenLockSet<'a, S> {
+    phantom: PhantomData<&'a mut S>,
+}
+
+impl<S: LockSet> sealed::Sealed for TakenLockSet<'_, S> {}
+
+impl<S: LockSet> HeldPriority for TakenLockSet<'_, S> {
+    const PRIORITY: usize = S::PRIORITY;
+}
+
+macro_rules! impl_lock_set {
+    ($n:literal; $($index:tt: $T:ident $U:ident $P:ident $guard:ident),+) => {
+        impl<'m, 'a, $($T: ?Sized, $U, const $P: usize),+> LockSet
+            for ($(&'m PriorityMutex<'a, $T, $U, $P>,)+)
+        {
+            const PRIORITY: usize = {
+                let mut max = 0;
+                $(if $P > max {
+                    max = $P;
+                })+
+                max
+            };
+
+            type Guards = ($(MutexGuard<'m, $T>,)+);
+
+            fn lock_all<'c, H: HeldPriority>(
+                self,
+                _previous_priority: PhantomData<&'c mut H>,
+            ) -> LockResult<(Self::Guards, TakenLockSet<'c, Self>)> {
+                let order = const { lock_set_order::<$n>([$($P),+], H::PRIORITY) };
+                let mut poisoned = false;
+                $(let mut $guard = None;)+
+                for index in order {
+                    match index {
+                        $($index => {
+                            let guard = self.$index.mutex.lock();
+                            poisoned |= guard.is_err();
+                            $guard = Some(guard.unwrap_or_else(PoisonError::into_inner));
+                        })+
+                        _ => unreachable!(),
+                    }
+                }
+                let locked = (
+                    ($($guard.unwrap(),)+),
+                    TakenLockSet {
+                        phantom: PhantomData,
+                    },
+                );
+                if poisoned {
+                    Err(PoisonError::new(locked))
+                } else {
+                    Ok(locked)
+                }
+            }
+        }
+    };
+}
+
+impl_lock_set!(2; 0: T0 U0 P0 guard0, 1: T1 U1 P1 guard1);
+impl_lock_set!(3; 0: T0 U0 P0 guard0, 1: T1 U1 P1 guard1, 2: T2 U2 P2 guard2);
+impl_lock_set!(4; 0: T0 U0 P0 guard0, 1: T1 U1 P1 guard1, 2: T2 U2 P2 guard2, 3: T3 U3 P3 guard3);
+
+/// Locks two to four `PriorityMutex`es in the order of their priorities,
+/// whatever order they are written in, and returns their guards in the
+/// written order together with a token of the highest priority. Like for
+/// `lock`, the token of the locks held so far has to be passed.
+///
+/// ```
+/// use type_state::{NoLocksHeld, PriorityMutex, lock_all, use_priority};
+///
+/// let m1 = PriorityMutex::<'_, u8, (), 1>::new(1);
+/// let m2 = PriorityMutex::<'_, u8, (), 2>::new(2);
+/// let m3 = PriorityMutex::<'_, u8, (), 3>::new(3);
+/// let m4 = PriorityMutex::<'_, u8, (), 4>::new(4);
+///
+/// let mut root = NoLocksHeld::root();
+/// let (mut at_m1, _guard1) = m1.lock(use_priority(&mut root)).unwrap();
+/// let ((g3, g2), mut token) = lock_all!((m3, m2), use_priority(&mut at_m1)).unwrap();
+/// let (_, g4) = m4.lock(use_priority(&mut token)).unwrap();
+/// assert_eq!(9, *g2 + *g3 + *g4);
+/// ```
+///
+/// The set has to come after the locks that are already held:
+///
+/// ```compile_fail
+/// use type_state::{NoLocksHeld, PriorityMutex, lock_all, use_priority};
+///
+/// let m1 = PriorityMutex::<'_, u8, (), 1>::new(1);
+/// let m2 = PriorityMutex::<'_, u8, (), 2>::new(2);
+/// let m4 = PriorityMutex::<'_, u8, (), 4>::new(4);
+///
+/// let mut root = NoLocksHeld::root();
+/// let (mut at_m4, _guard4) = m4.lock(use_priority(&mut root)).unwrap();
+/// let _locked = lock_all!((m1, m2), use_priority(&mut at_m4));
+/// ```
+///
+/// Two mutexes with the same priority can't be locked together:
+///
+/// ```compile_fail
+/// use type_state::{NoLocksHeld, PriorityMutex, lock_all, use_priority};
+///
+/// let first = PriorityMutex::<'_, u8, (), 1>::new(1);
+/// let second = PriorityMutex::<'_, u8, (), 1>::new(2);
+/// let mut root = NoLocksHeld::root();
+/// let _locked = lock_all!((first, second), use_priority(&mut root));
+/// ```
+#[macro_export]
+macro_rules! lock_all {
+    (($($mutex:expr),+ $(,)?), $previous_priority:expr) => {
+        $crate::LockSet::lock_all(($(&$mutex,)+), $previous_priority)
+    };
+}
+
+/// A condition variable for the guard of a `PriorityMutex`. Waiting releases
+/// and retakes the mutex but hands the token back, so locks of a higher
+/// priority can still be taken after the wait.
+#[derive(Debug, Default)]
+pub struct PriorityCondvar {
+    condvar: Condvar,
+}
+
+/// Token and guard of a locked `PriorityMutex`, as returned by `lock`.
+type Locked<'c, 'g, 'a, T, U, const PRIORITY: usize> = (
+    TakenLockPriority<'c, PriorityMutex<'a, T, U, PRIORITY>, PRIORITY>,
+    MutexGuard<'g, T>,
+);
+
+impl PriorityCondvar {
+    pub const fn new() -> Self {
+        PriorityCondvar {
+            condvar: Condvar::new(),
+        }
+    }
+
+    pub fn wait<'c, 'g, 'a, T, U, const PRIORITY: usize>(
+        &self,
+        (taken, guard): Locked<'c, 'g, 'a, T, U, PRIORITY>,
+    ) -> LockResult<Locked<'c, 'g, 'a, T, U, PRIORITY>> {
+        match self.condvar.wait(guard) {
+            Ok(guard) => Ok((taken, guard)),
+            Err(poisoned) => Err(PoisonError::new((taken, poisoned.into_inner()))),
+        }
+    }
+
+    pub fn wait_timeout<'c, 'g, 'a, T, U, const PRIORITY: usize>(
+        &self,
+        (taken, guard): Locked<'c, 'g, 'a, T, U, PRIORITY>,
+        timeout: Duration,
+    ) -> LockResult<(Locked<'c, 'g, 'a, T, U, PRIORITY>, WaitTimeoutResult)> {
+        match self.condvar.wait_timeout(guard, timeout) {
+            Ok((guard, result)) => Ok(((taken, guard), result)),
+            Err(poisoned) => {
+                let (guard, result) = poisoned.into_inner();
+                Err(PoisonError::new(((taken, guard), result)))
+            }
+        }
+    }
+
+    pub fn notify_one(&self) {
+        self.condvar.notify_one();
+    }
+
+    pub fn notify_all(&self) {
+        self.condvar.notify_all();
     }
 }
 
-pub fn use_priority<'a, 'b, V, const PREVIOUS_PRIORITY: usize>(
-    _priority: &'a mut TakenLockPriority<'b, V, PREVIOUS_PRIORITY>,
-) -> PhantomData<&'a mut TakenLockPriority<'b, V, PREVIOUS_PRIORITY>> {
+pub fn use_priority<H: HeldPriority>(_priority: &mut H) -> PhantomData<&mut H> {
     PhantomData
 }
 
 #[cfg(test)]
 mod test {
-    use std::{marker::PhantomData, sync::Mutex};
+    use std::sync::TryLockError;
+    use std::time::Duration;
 
-    use super::{PriorityMutex, TakenLockPriority, use_priority};
+    use super::{NoLocksHeld, PriorityCondvar, PriorityMutex, PriorityRwLock, use_priority};
 
     #[test]
     fn main() {
-        let mut root = TakenLockPriority::<'static, (), 0> {
-            phantom: PhantomData::<&mut _>,
-        };
-        let m1 = PriorityMutex::<'_, (), (), 1> {
-            previous: PhantomData::<&mut _>,
-            mutex: Mutex::new(()),
-        };
-        let m2 = PriorityMutex::<'_, (), (), 2> {
-            previous: PhantomData::<&mut _>,
-            mutex: Mutex::new(()),
-        };
+        let mut root = NoLocksHeld::root();
+        let m1 = PriorityMutex::<'_, (), (), 1>::new(());
+        let m2 = PriorityMutex::<'_, (), (), 2>::new(());
         {
-            let (mut _protector2, _guard2) = m2.lock(use_priority(&mut root));
-            let (_protector1, _guard1) = m1.lock(use_priority(&mut root));
+            let (mut _protector2, _guard2) = m2.lock(use_priority(&mut root)).unwrap();
+            let (_protector1, _guard1) = m1.lock(use_priority(&mut root)).unwrap();
         }
-        let (mut protector1, _guard1) = m1.lock(use_priority(&mut root));
-        let (_protector2, _guard2) = m2.lock(use_priority(&mut protector1));
+        let (mut protector1, _guard1) = m1.lock(use_priority(&mut root)).unwrap();
+        let (_protector2, _guard2) = m2.lock(use_priority(&mut protector1)).unwrap();
+    }
+
+    #[test]
+    fn rw_lock_readers_and_writers() {
+        let mut root = NoLocksHeld::root();
+        let config = PriorityRwLock::<'_, String, (), 1>::new("a".to_string());
+        let counter = PriorityMutex::<'_, usize, (), 2>::new(0);
+        {
+            let (_token, first) = config.read(use_priority(&mut root)).unwrap();
+            let (mut token, second) = config.read(use_priority(&mut root)).unwrap();
+            let (_, mut count) = counter.lock(use_priority(&mut token)).unwrap();
+            *count += first.len() + second.len();
+        }
+        {
+            let (mut token, mut value) = config.write(use_priority(&mut root)).unwrap();
+            value.push('b');
+            let (_, mut count) = counter.lock(use_priority(&mut token)).unwrap();
+            *count += value.len();
+        }
+        let (_, count) = counter.lock(use_priority(&mut root)).unwrap();
+        assert_eq!(4, *count);
+    }
+
+    #[test]
+    fn shared_between_threads() {
+        let counter = PriorityMutex::<'_, usize, (), 1>::new(0);
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    let mut root = NoLocksHeld::root();
+                    for _ in 0..100 {
+                        *counter.lock(use_priority(&mut root)).unwrap().1 += 1;
+                    }
+                });
+            }
+        });
+        let (_, count) = counter
+            .lock(use_priority(&mut NoLocksHeld::root()))
+            .unwrap();
+        assert_eq!(400, *count);
+    }
+
+    #[test]
+    fn recover_from_poisoning() {
+        let mut root = NoLocksHeld::root();
+        let outer = PriorityMutex::<'_, (), (), 1>::new(());
+        let inner = PriorityMutex::<'_, Vec<u8>, (), 2>::new(Vec::new());
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let mut root = NoLocksHeld::root();
+                    let (_, mut value) = inner.lock(use_priority(&mut root)).unwrap();
+                    value.push(1);
+                    panic!("poison the mutex");
+                })
+                .join()
+                .unwrap_err();
+        });
+        let (mut token, _guard) = outer.lock(use_priority(&mut root)).unwrap();
+        let Err(poisoned) = inner.lock(use_priority(&mut token)) else {
+            panic!("the panic did not poison the mutex");
+        };
+        let (_token, value) = poisoned.into_inner();
+        assert_eq!(vec![1], *value);
+        drop(value);
+        assert!(matches!(
+            inner.try_lock(use_priority(&mut token)),
+            Err(TryLockError::Poisoned(_))
+        ));
+    }
+
+    #[test]
+    fn try_lock_and_timeout() {
+        let mut root = NoLocksHeld::root();
+        let mutex = PriorityMutex::<'_, usize, (), 1>::new(0);
+        let (_, held) = mutex.try_lock(use_priority(&mut root)).unwrap();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let mut root = NoLocksHeld::root();
+                assert!(matches!(
+                    mutex.try_lock(use_priority(&mut root)),
+                    Err(TryLockError::WouldBlock)
+                ));
+                assert!(matches!(
+                    mutex.lock_timeout(use_priority(&mut root), Duration::from_millis(5)),
+                    Err(TryLockError::WouldBlock)
+                ));
+            });
+        });
+        std::thread::scope(|scope| {
+            let waiter = scope.spawn(|| {
+                let mut root = NoLocksHeld::root();
+                let (_, mut count) = mutex
+                    .lock_timeout(use_priority(&mut root), Duration::from_secs(10))
+                    .unwrap();
+                *count += 1;
+            });
+            std::thread::sleep(Duration::from_millis(10));
+            drop(held);
+            waiter.join().unwrap();
+        });
+        assert_eq!(1, *mutex.lock(use_priority(&mut root)).unwrap().1);
+    }
+
+    #[test]
+    fn condvar_keeps_the_token() {
+        let ready = PriorityMutex::<'_, bool, (), 1>::new(false);
+        let log = PriorityMutex::<'_, Vec<&str>, (), 2>::new(Vec::new());
+        let condvar = PriorityCondvar::new();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let mut root = NoLocksHeld::root();
+                let mut locked = ready.lock(use_priority(&mut root)).unwrap();
+                while !*locked.1 {
+                    locked = condvar.wait(locked).unwrap();
+                }
+                let (mut token, _ready) = locked;
+                log.lock(use_priority(&mut token)).unwrap().1.push("woken");
+            });
+            let mut root = NoLocksHeld::root();
+            let (mut token, mut ready) = ready.lock(use_priority(&mut root)).unwrap();
+            log.lock(use_priority(&mut token)).unwrap().1.push("ready");
+            *ready = true;
+            condvar.notify_all();
+        });
+        let (_, log) = log.lock(use_priority(&mut NoLocksHeld::root())).unwrap();
+        assert_eq!(vec!["ready", "woken"], *log);
+    }
+
+    #[test]
+    fn condvar_timeout() {
+        let mutex = PriorityMutex::<'_, (), (), 1>::new(());
+        let condvar = PriorityCondvar::new();
+        let mut root = NoLocksHeld::root();
+        let locked = mutex.lock(use_priority(&mut root)).unwrap();
+        let (_locked, result) = condvar
+            .wait_timeout(locked, Duration::from_millis(1))
+            .unwrap();
+        assert!(result.timed_out());
+    }
+
+    #[test]
+    fn lock_all_in_priority_order() {
+        let m1 = PriorityMutex::<'_, Vec<u8>, (), 1>::new(Vec::new());
+        let m2 = PriorityMutex::<'_, Vec<u8>, (), 2>::new(Vec::new());
+        let m3 = PriorityMutex::<'_, Vec<u8>, (), 3>::new(Vec::new());
+        let m4 = PriorityMutex::<'_, Vec<u8>, (), 4>::new(Vec::new());
+        std::thread::scope(|scope| {
+            for _ in 0..4 {
+                scope.spawn(|| {
+                    for i in 0..100 {
+                        // the opposite order of a plain chain of locks
+                        let mut root = NoLocksHeld::root();
+                        let ((mut g3, mut g2, mut g1), mut token) =
+                            lock_all!((m3, m2, m1), use_priority(&mut root)).unwrap();
+                        g1.push(i);
+                        g2.push(i);
+                        g3.push(i);
+                        m4.lock(use_priority(&mut token)).unwrap().1.push(i);
+                        drop((g1, g2, g3));
+                        let (mut token, mut g1) = m1.lock(use_priority(&mut root)).unwrap();
+                        let ((mut g4, mut g2), _) =
+                            lock_all!((m4, m2), use_priority(&mut token)).unwrap();
+                        g1.pop();
+                        g2.pop();
+                        g4.pop();
+                    }
+                });
+            }
+        });
+        let mut root = NoLocksHeld::root();
+        let ((g1, g4, g2, g3), _) = lock_all!((m1, m4, m2, m3), use_priority(&mut root)).unwrap();
+        assert_eq!((0, 0, 400, 0), (g1.len(), g2.len(), g3.len(), g4.len()));
+    }
+
+    #[test]
+    fn lock_all_reports_poisoning() {
+        let m1 = PriorityMutex::<'_, u8, (), 1>::new(1);
+        let m2 = PriorityMutex::<'_, u8, (), 2>::new(2);
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    let _guard = m2.lock(use_priority(&mut NoLocksHeld::root()));
+                    panic!("poison the mutex");
+                })
+                .join()
+                .unwrap_err();
+        });
+        let mut root = NoLocksHeld::root();
+        let Err(poisoned) = lock_all!((m2, m1), use_priority(&mut root)) else {
+            panic!("the panic did not poison the mutex");
+        };
+        let ((g2, g1), _) = poisoned.into_inner();
+        assert_eq!((2, 1), (*g2, *g1));
     }
 }
@@ -0,0 +1,942 @@
+use std::error::Error;
+use std::fmt::Display;
+
+// Incremental HTTP/1.1 request parser. Bytes are fed in as they arrive and
+// the parser moves from RequestLine over RequestHeaders to RequestBody, each
+// state only offers the transition to the next one.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub max_request_line: usize,
+    pub max_headers: usize,
+    /// size of all header lines together, without the request line
+    pub max_header_bytes: usize,
+    pub max_body: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_request_line: 8 * 1024,
+            max_headers: 100,
+            max_header_bytes: 64 * 1024,
+            max_body: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// a line ended in a bare LF instead of CRLF
+    InvalidLineEnding,
+    RequestLineTooLong,
+    InvalidRequestLine(String),
+    UnknownMethod(String),
+    InvalidTarget(String),
+    UnsupportedVersion(String),
+    InvalidHeader(String),
+    TooManyHeaders,
+    HeadersTooLarge,
+    InvalidContentLength(String),
+    UnsupportedTransferEncoding(String),
+    /// both Content-Length and Transfer-Encoding are present
+    ConflictingFraming,
+    InvalidChunk(String),
+    BodyTooLarge,
+    InvalidStatusLine(String),
+    /// the message ended before it was complete
+    UnexpectedEnd,
+    /// bytes left after a complete message
+    TrailingData(usize),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::InvalidLineEnding => write!(f, "line not terminated by CRLF"),
+            ParseError::RequestLineTooLong => write!(f, "request line too long"),
+            ParseError::InvalidRequestLine(line) => write!(f, "invalid request line {line:?}"),
+            ParseError::UnknownMethod(method) => write!(f, "unknown method {method:?}"),
+            ParseError::InvalidTarget(target) => write!(f, "invalid request target {target:?}"),
+            ParseError::UnsupportedVersion(version) => {
+                write!(f, "unsupported HTTP version {version:?}")
+            }
+            ParseError::InvalidHeader(line) => write!(f, "invalid header line {line:?}"),
+            ParseError::TooManyHeaders => write!(f, "too many header fields"),
+            ParseError::HeadersTooLarge => write!(f, "header section too large"),
+            ParseError::InvalidContentLength(value) => {
+                write!(f, "invalid Content-Length {value:?}")
+            }
+            ParseError::UnsupportedTransferEncoding(value) => {
+                write!(f, "unsupported Transfer-Encoding {value:?}")
+            }
+            ParseError::ConflictingFraming => {
+                write!(f, "both Content-Length and Transfer-Encoding are set")
+            }
+            ParseError::InvalidChunk(reason) => write!(f, "invalid chunk: {reason}"),
+            ParseError::BodyTooLarge => write!(f, "body too large"),
+            ParseError::InvalidStatusLine(line) => write!(f, "invalid status line {line:?}"),
+            ParseError::UnexpectedEnd => write!(f, "message ended early"),
+            ParseError::TrailingData(n) => write!(f, "{n} bytes after the end of the message"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+}
+
+impl Method {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Connect => "CONNECT",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Patch => "PATCH",
+        }
+    }
+
+    fn parse(method: &str) -> Result<Method, ParseError> {
+        Ok(match method {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "CONNECT" => Method::Connect,
+            "OPTIONS" => Method::Options,
+            "TRACE" => Method::Trace,
+            "PATCH" => Method::Patch,
+            _ => return Err(ParseError::UnknownMethod(method.to_string())),
+        })
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The request target in one of the four forms of RFC 9112, section 3.2.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RequestTarget {
+    /// `/path?query`
+    Origin(String),
+    /// `http://host/path`, used towards proxies
+    Absolute(String),
+    /// `host:port`, only for CONNECT
+    Authority(String),
+    /// `*`, only for OPTIONS
+    Asterisk,
+}
+
+impl RequestTarget {
+    fn parse(method: Method, target: &str) -> Result<RequestTarget, ParseError> {
+        let invalid = || ParseError::InvalidTarget(target.to_string());
+        if target.is_empty() || !target.bytes().all(|c| c.is_ascii_graphic()) {
+            return Err(invalid());
+        }
+        match method {
+            Method::Connect => return Ok(RequestTarget::Authority(target.to_string())),
+            Method::Options if target == "*" => return Ok(RequestTarget::Asterisk),
+            _ => {}
+        }
+        if target.starts_with('/') {
+            Ok(RequestTarget::Origin(target.to_string()))
+        } else if target.contains("://") {
+            Ok(RequestTarget::Absolute(target.to_string()))
+        } else {
+            Err(invalid())
+        }
+    }
+
+    /// Path of an origin-form target without the query.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            RequestTarget::Origin(target) => target.split('?').next(),
+            _ => None,
+        }
+    }
+
+    pub fn query(&self) -> Option<&str> {
+        match self {
+            RequestTarget::Origin(target) => target.split_once('?').map(|(_, query)| query),
+            _ => None,
+        }
+    }
+}
+
+impl Display for RequestTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RequestTarget::Origin(target)
+            | RequestTarget::Absolute(target)
+            | RequestTarget::Authority(target) => f.write_str(target),
+            RequestTarget::Asterisk => f.write_str("*"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl Version {
+    fn parse(version: &str) -> Result<Version, ParseError> {
+        match version {
+            "HTTP/1.0" => Ok(Version::Http10),
+            "HTTP/1.1" => Ok(Version::Http11),
+            _ => Err(ParseError::UnsupportedVersion(version.to_string())),
+        }
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Version::Http10 => f.write_str("HTTP/1.0"),
+            Version::Http11 => f.write_str("HTTP/1.1"),
+        }
+    }
+}
+
+/// A completely parsed request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HttpRequest {
+    method: Method,
+    target: RequestTarget,
+    version: Version,
+    headers: Vec<(String, String)>,
+    trailers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl HttpRequest {
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    pub fn target(&self) -> &RequestTarget {
+        &self.target
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// First header named `name`, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Trailer fields sent after a chunked body.
+    pub fn trailers(&self) -> &[(String, String)] {
+        &self.trailers
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+// tchar of RFC 9110, section 5.6.2
+pub(crate) fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s.bytes()
+            .all(|c| c.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&c))
+}
+
+// field values may contain visible characters, spaces and tabs
+pub(crate) fn is_field_value(s: &str) -> bool {
+    s.bytes()
+        .all(|c| c == b' ' || c == b'\t' || c >= 0x80 || c.is_ascii_graphic())
+}
+
+pub(crate) fn parse_header(line: &str) -> Result<(String, String), ParseError> {
+    let invalid = || ParseError::InvalidHeader(line.to_string());
+    let (name, value) = line.split_once(':').ok_or_else(invalid)?;
+    // whitespace before the colon or a line folded with leading whitespace
+    // are both rejected by is_token
+    if !is_token(name) {
+        return Err(invalid());
+    }
+    let value = value.trim_matches([' ', '\t']);
+    if !is_field_value(value) {
+        return Err(invalid());
+    }
+    Ok((name.to_string(), value.to_string()))
+}
+
+// Returns the line at the start of `buf` without its CRLF, or None if the
+// line is not complete yet.
+pub(crate) fn take_line(buf: &mut Vec<u8>) -> Result<Option<String>, ParseError> {
+    let Some(end) = buf.iter().position(|c| *c == b'\n') else {
+        return Ok(None);
+    };
+    if end == 0 || buf[end - 1] != b'\r' {
+        return Err(ParseError::InvalidLineEnding);
+    }
+    let line = String::from_utf8_lossy(&buf[..end - 1]).into_owned();
+    buf.drain(..=end);
+    Ok(Some(line))
+}
+
+// length of the line at the start of `buf` so far, including an already
+// received CRLF
+pub(crate) fn line_len(buf: &[u8]) -> usize {
+    buf.iter()
+        .position(|c| *c == b'\n')
+        .map_or(buf.len(), |end| end + 1)
+}
+
+pub trait ParseState {}
+
+/// Outcome of feeding bytes to a parser: either more bytes are needed or the
+/// parser moved on to its next state.
+pub enum Step<S, N> {
+    Pending(S),
+    Done(N),
+}
+
+pub struct RequestLine {}
+impl ParseState for RequestLine {}
+
+pub struct RequestHeaders {
+    method: Method,
+    target: RequestTarget,
+    version: Version,
+    headers: Vec<(String, String)>,
+    header_bytes: usize,
+}
+impl ParseState for RequestHeaders {}
+
+enum Framing {
+    Length(usize),
+    Chunked(Chunk),
+}
+
+enum Chunk {
+    Size,
+    Data(usize),
+    DataEnd,
+    Trailers,
+}
+
+// Reads a body framed by Content-Length or chunked transfer coding, shared
+// with the response parser.
+pub(crate) struct BodyReader {
+    framing: Framing,
+    trailers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+pub struct RequestBody {
+    head: RequestHeaders,
+    reader: BodyReader,
+}
+impl ParseState for RequestBody {}
+
+pub struct RequestParser<S: ParseState> {
+    buf: Vec<u8>,
+    limits: Limits,
+    _parse_state: S,
+}
+
+impl<S: ParseState> RequestParser<S> {
+    /// Number of received bytes that were not consumed yet.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+}
+
+impl Default for RequestParser<RequestLine> {
+    fn default() -> Self {
+        RequestParser::new(Limits::default())
+    }
+}
+
+impl RequestParser<RequestLine> {
+    pub fn new(limits: Limits) -> Self {
+        RequestParser {
+            buf: Vec::new(),
+            limits,
+            _parse_state: RequestLine {},
+        }
+    }
+
+    pub fn feed(
+        mut self,
+        bytes: &[u8],
+    ) -> Result<Step<Self, RequestParser<RequestHeaders>>, ParseError> {
+        self.buf.extend_from_slice(bytes);
+        // empty lines in front of a request are ignored, RFC 9112 section 2.2
+        while self.buf.starts_with(b"\r\n") {
+            self.buf.drain(..2);
+        }
+        if line_len(&self.buf) > self.limits.max_request_line + 2 {
+            return Err(ParseError::RequestLineTooLong);
+        }
+        let Some(line) = take_line(&mut self.buf)? else {
+            return Ok(Step::Pending(self));
+        };
+        let [method, target, version] = line.split(' ').collect::<Vec<_>>()[..] else {
+            return Err(ParseError::InvalidRequestLine(line));
+        };
+        let method = Method::parse(method)?;
+        let target = RequestTarget::parse(method, target)?;
+        let version = Version::parse(version)?;
+        let headers = RequestHeaders {
+            method,
+            target,
+            version,
+            headers: Vec::new(),
+            header_bytes: 0,
+        };
+        Ok(Step::Done(RequestParser {
+            buf: self.buf,
+            limits: self.limits,
+            _parse_state: headers,
+        }))
+    }
+}
+
+impl RequestParser<RequestHeaders> {
+    pub fn method(&self) -> Method {
+        self._parse_state.method
+    }
+
+    pub fn target(&self) -> &RequestTarget {
+        &self._parse_state.target
+    }
+
+    pub fn version(&self) -> Version {
+        self._parse_state.version
+    }
+
+    pub fn feed(
+        mut self,
+        bytes: &[u8],
+    ) -> Result<Step<Self, RequestParser<RequestBody>>, ParseError> {
+        self.buf.extend_from_slice(bytes);
+        loop {
+            let header_bytes = self._parse_state.header_bytes + line_len(&self.buf);
+            if header_bytes > self.limits.max_header_bytes {
+                return Err(ParseError::HeadersTooLarge);
+            }
+            let Some(line) = take_line(&mut self.buf)? else {
+                return Ok(Step::Pending(self));
+            };
+            if line.is_empty() {
+                let reader = BodyReader::new(&self._parse_state.headers, &self.limits)?;
+                let body = RequestBody {
+                    head: self._parse_state,
+                    reader,
+                };
+                return Ok(Step::Done(RequestParser {
+                    buf: self.buf,
+                    limits: self.limits,
+                    _parse_state: body,
+                }));
+            }
+            if self._parse_state.headers.len() == self.limits.max_headers {
+                return Err(ParseError::TooManyHeaders);
+            }
+            self._parse_state.headers.push(parse_header(&line)?);
+            self._parse_state.header_bytes = header_bytes;
+        }
+    }
+}
+
+fn framing(headers: &[(String, String)], limits: &Limits) -> Result<Option<Framing>, ParseError> {
+    let mut lengths = headers
+        .iter()
+        .filter(|(key, _)| key.eq_ignore_ascii_case("Content-Length"))
+        .map(|(_, value)| value);
+    let length = match lengths.next() {
+        Some(first) => {
+            if lengths.any(|other| other != first) {
+                return Err(ParseError::InvalidContentLength(first.clone()));
+            }
+            if first.is_empty() || !first.bytes().all(|c| c.is_ascii_digit()) {
+                return Err(ParseError::InvalidContentLength(first.clone()));
+            }
+            let length = first
+                .parse::<usize>()
+                .map_err(|_| ParseError::BodyTooLarge)?;
+            Some(length)
+        }
+        None => None,
+    };
+    match (find_header(headers, "Transfer-Encoding"), length) {
+        (Some(_), Some(_)) => Err(ParseError::ConflictingFraming),
+        (Some(encoding), None) if encoding.eq_ignore_ascii_case("chunked") => {
+            Ok(Some(Framing::Chunked(Chunk::Size)))
+        }
+        (Some(encoding), None) => Err(ParseError::UnsupportedTransferEncoding(
+            encoding.to_string(),
+        )),
+        (None, Some(length)) if length > limits.max_body => Err(ParseError::BodyTooLarge),
+        (None, length) => Ok(length.map(Framing::Length)),
+    }
+}
+
+impl BodyReader {
+    /// A reader for the body announced by `headers`, a body without
+    /// Content-Length or Transfer-Encoding is empty.
+    pub(crate) fn new(
+        headers: &[(String, String)],
+        limits: &Limits,
+    ) -> Result<BodyReader, ParseError> {
+        Ok(BodyReader {
+            framing: framing(headers, limits)?.unwrap_or(Framing::Length(0)),
+            trailers: Vec::new(),
+            body: Vec::new(),
+        })
+    }
+
+    /// Whether `headers` announce a body at all.
+    pub(crate) fn is_framed(headers: &[(String, String)]) -> bool {
+        find_header(headers, "Content-Length").is_some()
+            || find_header(headers, "Transfer-Encoding").is_some()
+    }
+
+    pub(crate) fn into_parts(self) -> (Vec<u8>, Vec<(String, String)>) {
+        (self.body, self.trailers)
+    }
+
+    // Consumes body bytes from `buf`, returns true once the whole body was
+    // read. Trailers count towards the header limits, `header_count` and
+    // `header_bytes` are what the header section already used.
+    pub(crate) fn read(
+        &mut self,
+        buf: &mut Vec<u8>,
+        limits: &Limits,
+        header_count: usize,
+        header_bytes: &mut usize,
+    ) -> Result<bool, ParseError> {
+        loop {
+            match &mut self.framing {
+                Framing::Length(remaining) => {
+                    let n = (*remaining).min(buf.len());
+                    self.body.extend(buf.drain(..n));
+                    *remaining -= n;
+                    return Ok(*remaining == 0);
+                }
+                Framing::Chunked(Chunk::Size) => {
+                    // generous bound for a hex size and chunk extensions
+                    if line_len(buf) > 1024 {
+                        return Err(ParseError::InvalidChunk("size line too long".to_string()));
+                    }
+                    let Some(line) = take_line(buf)? else {
+                        return Ok(false);
+                    };
+                    let size = line.split(';').next().unwrap_or_default();
+                    let size = usize::from_str_radix(size, 16)
+                        .ok()
+                        .filter(|_| size.bytes().all(|c| c.is_ascii_hexdigit()))
+                        .ok_or_else(|| ParseError::InvalidChunk(format!("size {size:?}")))?;
+                    if size > limits.max_body - self.body.len() {
+                        return Err(ParseError::BodyTooLarge);
+                    }
+                    self.framing = Framing::Chunked(match size {
+                        0 => Chunk::Trailers,
+                        size => Chunk::Data(size),
+                    });
+                }
+                Framing::Chunked(Chunk::Data(remaining)) => {
+                    let n = (*remaining).min(buf.len());
+                    self.body.extend(buf.drain(..n));
+                    *remaining -= n;
+                    if *remaining > 0 {
+                        return Ok(false);
+                    }
+                    self.framing = Framing::Chunked(Chunk::DataEnd);
+                }
+                Framing::Chunked(Chunk::DataEnd) => {
+                    if buf.len() < 2 {
+                        return Ok(false);
+                    }
+                    if !buf.starts_with(b"\r\n") {
+                        return Err(ParseError::InvalidChunk(
+                            "data not terminated by CRLF".to_string(),
+                        ));
+                    }
+                    buf.drain(..2);
+                    self.framing = Framing::Chunked(Chunk::Size);
+                }
+                Framing::Chunked(Chunk::Trailers) => {
+                    let new_header_bytes = *header_bytes + line_len(buf);
+                    if new_header_bytes > limits.max_header_bytes {
+                        return Err(ParseError::HeadersTooLarge);
+                    }
+                    let Some(line) = take_line(buf)? else {
+                        return Ok(false);
+                    };
+                    if line.is_empty() {
+                        return Ok(true);
+                    }
+                    if header_count + self.trailers.len() == limits.max_headers {
+                        return Err(ParseError::TooManyHeaders);
+                    }
+                    self.trailers.push(parse_header(&line)?);
+                    *header_bytes = new_header_bytes;
+                }
+            }
+        }
+    }
+}
+
+impl RequestParser<RequestBody> {
+    pub fn method(&self) -> Method {
+        self._parse_state.head.method
+    }
+
+    pub fn target(&self) -> &RequestTarget {
+        &self._parse_state.head.target
+    }
+
+    pub fn version(&self) -> Version {
+        self._parse_state.head.version
+    }
+
+    pub fn headers(&self) -> &[(String, String)] {
+        &self._parse_state.head.headers
+    }
+
+    /// Once the body is complete the request is returned together with a
+    /// parser for the next request on the same connection, which already
+    /// holds the bytes received after this request.
+    pub fn feed(
+        mut self,
+        bytes: &[u8],
+    ) -> Result<Step<Self, (HttpRequest, RequestParser<RequestLine>)>, ParseError> {
+        self.buf.extend_from_slice(bytes);
+        let state = &mut self._parse_state;
+        let header_count = state.head.headers.len();
+        let read = state.reader.read(
+            &mut self.buf,
+            &self.limits,
+            header_count,
+            &mut state.head.header_bytes,
+        )?;
+        if !read {
+            return Ok(Step::Pending(self));
+        }
+        let RequestBody { head, reader } = self._parse_state;
+        let (body, trailers) = reader.into_parts();
+        let request = HttpRequest {
+            method: head.method,
+            target: head.target,
+            version: head.version,
+            headers: head.headers,
+            trailers,
+            body,
+        };
+        let parser = RequestParser {
+            buf: self.buf,
+            limits: self.limits,
+            _parse_state: RequestLine {},
+        };
+        Ok(Step::Done((request, parser)))
+    }
+}
+
+/// Parses one request that was received completely, returns the request and
+/// the number of bytes it occupied, or `None` if `bytes` ends early.
+pub fn parse_request(
+    bytes: &[u8],
+    limits: Limits,
+) -> Result<Option<(HttpRequest, usize)>, ParseError> {
+    let parser = match RequestParser::new(limits).feed(bytes)? {
+        Step::Pending(_) => return Ok(None),
+        Step::Done(parser) => parser,
+    };
+    let parser = match parser.feed(&[])? {
+        Step::Pending(_) => return Ok(None),
+        Step::Done(parser) => parser,
+    };
+    match parser.feed(&[])? {
+        Step::Pending(_) => Ok(None),
+        Step::Done((request, next)) => Ok(Some((request, bytes.len() - next.buf.len()))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        HttpRequest, Limits, Method, ParseError, RequestLine, RequestParser, RequestTarget, Step,
+        Version, parse_request,
+    };
+
+    fn parse(bytes: &[u8]) -> Result<Option<(HttpRequest, usize)>, ParseError> {
+        parse_request(bytes, Limits::default())
+    }
+
+    fn parse_complete(bytes: &[u8]) -> HttpRequest {
+        let (request, len) = parse(bytes).unwrap().unwrap();
+        assert_eq!(bytes.len(), len);
+        request
+    }
+
+    #[test]
+    fn parse_get() {
+        let request =
+            parse_complete(b"GET /index.html?lang=de HTTP/1.1\r\nHost: example.com\r\n\r\n");
+        assert_eq!(Method::Get, request.method());
+        assert_eq!(
+            &RequestTarget::Origin("/index.html?lang=de".to_string()),
+            request.target()
+        );
+        assert_eq!(Some("/index.html"), request.target().path());
+        assert_eq!(Some("lang=de"), request.target().query());
+        assert_eq!(Version::Http11, request.version());
+        assert_eq!(Some("example.com"), request.header("host"));
+        assert_eq!(b"", request.body());
+    }
+
+    #[test]
+    fn parse_content_length_body() {
+        let request = parse_complete(b"POST /add HTTP/1.0\r\nContent-Length: 5\r\n\r\n1 + 2");
+        assert_eq!(Method::Post, request.method());
+        assert_eq!(Version::Http10, request.version());
+        assert_eq!(b"1 + 2", request.body());
+    }
+
+    #[test]
+    fn parse_chunked_body_with_trailers() {
+        let request = parse_complete(
+            b"PUT /blob HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+              4\r\nWiki\r\n6;name=value\r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\n\
+              Expires: never\r\n\r\n",
+        );
+        assert_eq!(b"Wikipedia in \r\n\r\nchunks.", request.body());
+        assert_eq!(
+            [("Expires".to_string(), "never".to_string())],
+            request.trailers()
+        );
+    }
+
+    #[test]
+    fn special_targets() {
+        let request = parse_complete(b"OPTIONS * HTTP/1.1\r\n\r\n");
+        assert_eq!(&RequestTarget::Asterisk, request.target());
+        let request = parse_complete(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n");
+        assert_eq!(
+            &RequestTarget::Authority("example.com:443".to_string()),
+            request.target()
+        );
+        let request = parse_complete(b"GET http://example.com/ HTTP/1.1\r\n\r\n");
+        assert_eq!(
+            &RequestTarget::Absolute("http://example.com/".to_string()),
+            request.target()
+        );
+        assert_eq!(None, request.target().path());
+    }
+
+    #[test]
+    fn feed_byte_by_byte() {
+        let bytes =
+            b"POST /add HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+        let mut bytes = bytes.iter().map(std::slice::from_ref);
+
+        let mut line = RequestParser::default();
+        let mut headers = loop {
+            match line.feed(bytes.next().unwrap()).unwrap() {
+                Step::Pending(parser) => line = parser,
+                Step::Done(parser) => break parser,
+            }
+        };
+        assert_eq!(Method::Post, headers.method());
+        let mut body = loop {
+            match headers.feed(bytes.next().unwrap()).unwrap() {
+                Step::Pending(parser) => headers = parser,
+                Step::Done(parser) => break parser,
+            }
+        };
+        assert_eq!(1, body.headers().len());
+        let request = loop {
+            match body.feed(bytes.next().unwrap()).unwrap() {
+                Step::Pending(parser) => body = parser,
+                Step::Done((request, _)) => break request,
+            }
+        };
+        assert_eq!(b"abc", request.body());
+        assert_eq!(None, bytes.next());
+    }
+
+    #[test]
+    fn pipelined_requests() {
+        let bytes = b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\n\r\n";
+        let mut paths = Vec::new();
+        let mut line: RequestParser<RequestLine> = RequestParser::default();
+        let mut input: &[u8] = bytes;
+        while paths.len() < 2 {
+            let Step::Done(headers) = line.feed(input).unwrap() else {
+                panic!("request line incomplete");
+            };
+            let Step::Done(body) = headers.feed(&[]).unwrap() else {
+                panic!("headers incomplete");
+            };
+            let Step::Done((request, next)) = body.feed(&[]).unwrap() else {
+                panic!("body incomplete");
+            };
+            paths.push(request.target().to_string());
+            line = next;
+            input = &[];
+        }
+        assert_eq!(["/a", "/b"], paths[..]);
+        let (_, len) = parse(bytes).unwrap().unwrap();
+        assert_eq!(bytes.len() / 2, len);
+    }
+
+    #[test]
+    fn incomplete_requests() {
+        let bytes = b"POST / HTTP/1.1\r\nContent-Length: 3\r\n\r\nabc";
+        for len in 0..bytes.len() {
+            assert_eq!(None, parse(&bytes[..len]).unwrap());
+        }
+    }
+
+    #[test]
+    fn request_line_errors() {
+        let cases: [(&[u8], ParseError); 6] = [
+            (
+                b"GET /\r\n\r\n",
+                ParseError::InvalidRequestLine("GET /".to_string()),
+            ),
+            (
+                b"BREW /pot HTTP/1.1\r\n\r\n",
+                ParseError::UnknownMethod("BREW".to_string()),
+            ),
+            (
+                b"GET index.html HTTP/1.1\r\n\r\n",
+                ParseError::InvalidTarget("index.html".to_string()),
+            ),
+            (
+                b"GET / HTTP/2.0\r\n\r\n",
+                ParseError::UnsupportedVersion("HTTP/2.0".to_string()),
+            ),
+            (b"GET / HTTP/1.1\n\n", ParseError::InvalidLineEnding),
+            (
+                b"GET /  HTTP/1.1\r\n\r\n",
+                ParseError::InvalidRequestLine("GET /  HTTP/1.1".to_string()),
+            ),
+        ];
+        for (bytes, error) in cases {
+            assert_eq!(Err(error), parse(bytes).map(|_| ()));
+        }
+    }
+
+    #[test]
+    fn header_errors() {
+        let cases: [(&[u8], ParseError); 6] = [
+            (
+                b"GET / HTTP/1.1\r\nHost : a\r\n\r\n",
+                ParseError::InvalidHeader("Host : a".to_string()),
+            ),
+            (
+                b"GET / HTTP/1.1\r\nHost: a\r\n folded\r\n\r\n",
+                ParseError::InvalidHeader(" folded".to_string()),
+            ),
+            (
+                b"GET / HTTP/1.1\r\nno colon\r\n\r\n",
+                ParseError::InvalidHeader("no colon".to_string()),
+            ),
+            (
+                b"POST / HTTP/1.1\r\nContent-Length: -1\r\n\r\n",
+                ParseError::InvalidContentLength("-1".to_string()),
+            ),
+            (
+                b"POST / HTTP/1.1\r\nContent-Length: 1\r\nTransfer-Encoding: chunked\r\n\r\n",
+                ParseError::ConflictingFraming,
+            ),
+            (
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n",
+                ParseError::UnsupportedTransferEncoding("gzip".to_string()),
+            ),
+        ];
+        for (bytes, error) in cases {
+            assert_eq!(Err(error), parse(bytes).map(|_| ()));
+        }
+    }
+
+    #[test]
+    fn chunk_errors() {
+        let cases: [(&[u8], ParseError); 2] = [
+            (
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nx\r\n",
+                ParseError::InvalidChunk("size \"x\"".to_string()),
+            ),
+            (
+                b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nab\r\n",
+                ParseError::InvalidChunk("data not terminated by CRLF".to_string()),
+            ),
+        ];
+        for (bytes, error) in cases {
+            assert_eq!(Err(error), parse(bytes).map(|_| ()));
+        }
+    }
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            max_request_line: 16,
+            max_headers: 2,
+            max_header_bytes: 32,
+            max_body: 4,
+        };
+        let parse = |bytes: &[u8]| parse_request(bytes, limits).map(|_| ());
+
+        assert_eq!(
+            Err(ParseError::RequestLineTooLong),
+            parse(b"GET /very/long/path")
+        );
+        assert_eq!(Ok(()), parse(b"GET / HTTP/1.1\r\n"));
+        assert_eq!(
+            Err(ParseError::TooManyHeaders),
+            parse(b"GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n")
+        );
+        assert_eq!(
+            Err(ParseError::HeadersTooLarge),
+            parse(b"GET / HTTP/1.1\r\nA: 0123456789012345678901234567890123456789")
+        );
+        assert_eq!(
+            Err(ParseError::BodyTooLarge),
+            parse(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\n")
+        );
+        assert_eq!(
+            Err(ParseError::BodyTooLarge),
+            parse(b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\n")
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use crate::html::{Body, Headers, HttpResponse};
+use crate::request::{
+    BodyReader, Limits, ParseError, is_field_value, line_len, parse_header, take_line,
+};
+use crate::status::StatusCode;
+
+/// A received response, with a body unless the response has none.
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParsedResponse {
+    Headers(HttpResponse<Headers>),
+    Body(HttpResponse<Body<Vec<u8>>>),
+}
+
+fn parse_status_line(line: &str) -> Result<(StatusCode, String), ParseError> {
+    let invalid = || ParseError::InvalidStatusLine(line.to_string());
+    let (version, rest) = line.split_once(' ').ok_or_else(invalid)?;
+    if version != "HTTP/1.1" && version != "HTTP/1.0" {
+        return Err(ParseError::UnsupportedVersion(version.to_string()));
+    }
+    // the space in front of an empty reason phrase is often left out
+    let (code, reason) = rest.split_once(' ').unwrap_or((rest, ""));
+    if code.len() != 3 || !code.bytes().all(|c| c.is_ascii_digit()) || !is_field_value(reason) {
+        return Err(invalid());
+    }
+    let code = code
+        .parse()
+        .ok()
+        .and_then(|code| StatusCode::new(code).ok())
+        .ok_or_else(invalid)?;
+    Ok((code, reason.to_string()))
+}
+
+// responses to which RFC 9112 section 6.3 forbids a body
+pub(crate) fn has_no_body(code: StatusCode) -> bool {
+    code.is_informational() || code == StatusCode::NO_CONTENT || code == StatusCode::NOT_MODIFIED
+}
+
+/// Parses a complete response, e.g. everything read from a connection until
+/// the server closed it. A response without `Content-Length` or
+/// `Transfer-Encoding` has a body if any bytes follow its head. Trailers of a
+/// chunked body are appended to the headers.
+pub fn parse_response(bytes: &[u8], limits: Limits) -> Result<ParsedResponse, ParseError> {
+    let mut buf = bytes.to_vec();
+    let status_line = take_line(&mut buf)?.ok_or(ParseError::UnexpectedEnd)?;
+    let status_line = parse_status_line(&status_line)?;
+
+    let mut headers = Vec::new();
+    let mut header_bytes = 0;
+    loop {
+        header_bytes += line_len(&buf);
+        if header_bytes > limits.max_header_bytes {
+            return Err(ParseError::HeadersTooLarge);
+        }
+        let line = take_line(&mut buf)?.ok_or(ParseError::UnexpectedEnd)?;
+        if line.is_empty() {
+            break;
+        }
+        if headers.len() == limits.max_headers {
+            return Err(ParseError::TooManyHeaders);
+        }
+        headers.push(parse_header(&line)?);
+    }
+
+    let framed = BodyReader::is_framed(&headers);
+    if has_no_body(status_line.0) || (!framed && buf.is_empty()) {
+        if !buf.is_empty() {
+            return Err(ParseError::TrailingData(buf.len()));
+        }
+        return Ok(ParsedResponse::Headers(HttpResponse::parsed(
+            status_line,
+            headers,
+        )));
+    }
+
+    let body = if framed {
+        let mut reader = BodyReader::new(&headers, &limits)?;
+        if !reader.read(&mut buf, &limits, headers.len(), &mut header_bytes)? {
+            return Err(ParseError::UnexpectedEnd);
+        }
+        if !buf.is_empty() {
+            return Err(ParseError::TrailingData(buf.len()));
+        }
+        let (body, trailers) = reader.into_parts();
+        headers.extend(trailers);
+        body
+    } else if buf.len() > limits.max_body {
+        return Err(ParseError::BodyTooLarge);
+    } else {
+        buf
+    };
+    let response = HttpResponse::parsed(status_line, headers).with_parsed_body(body);
+    Ok(ParsedResponse::Body(response))
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use proptest::prelude::*;
+
+    use super::{ParsedResponse, parse_response};
+    use crate::html::{Body, Headers, HttpResponse};
+    use crate::request::{Limits, ParseError};
+    use crate::status::StatusCode;
+
+    fn parse(bytes: &[u8]) -> Result<ParsedResponse, ParseError> {
+        parse_response(bytes, Limits::default())
+    }
+
+    fn parse_body(bytes: &[u8]) -> HttpResponse<Body<Vec<u8>>> {
+        match parse(bytes).unwrap() {
+            ParsedResponse::Body(response) => response,
+            ParsedResponse::Headers(response) => panic!("no body in {response}"),
+        }
+    }
+
+    #[test]
+    fn accessors() {
+        let response = parse_body(
+            b"HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\n\
+              Content-Length: 4\r\n\r\nnope",
+        );
+        assert_eq!(StatusCode::NOT_FOUND, response.status_code());
+        assert_eq!("Not Found", response.reason());
+        assert_eq!(Some("text/plain"), response.get_header("content-type"));
+        assert_eq!(2, response.headers().len());
+        assert_eq!(b"nope", &response.get_body()[..]);
+    }
+
+    #[test]
+    fn without_body() {
+        let ParsedResponse::Headers(response) = parse(b"HTTP/1.1 204 No Content\r\n\r\n").unwrap()
+        else {
+            panic!("unexpected body");
+        };
+        assert_eq!(StatusCode::NO_CONTENT, response.status_code());
+        assert_eq!(
+            Ok(ParsedResponse::Headers(
+                HttpResponse::default().status(StatusCode::OK)
+            )),
+            parse(b"HTTP/1.1 200 OK\r\n\r\n")
+        );
+    }
+
+    #[test]
+    fn body_until_end_of_input() {
+        let response = parse_body(b"HTTP/1.0 200 OK\r\n\r\nread until close");
+        assert_eq!(b"read until close", &response.get_body()[..]);
+    }
+
+    #[test]
+    fn chunked_body_with_trailers() {
+        let mut stream = HttpResponse::default()
+            .status(StatusCode::OK)
+            .stream(Vec::new())
+            .unwrap();
+        stream.write_all(b"Wiki").unwrap();
+        stream.write_all(b"pedia").unwrap();
+        stream.trailer("Checksum", "1234").unwrap();
+        let sent = stream.finish().unwrap().into_inner();
+
+        let response = parse_body(&sent);
+        assert_eq!(b"Wikipedia", &response.get_body()[..]);
+        assert_eq!(Some("chunked"), response.get_header("Transfer-Encoding"));
+        assert_eq!(Some("1234"), response.get_header("Checksum"));
+    }
+
+    #[test]
+    fn malformed_responses() {
+        let cases: [(&[u8], ParseError); 6] = [
+            (
+                b"HTTP/1.1 2000 OK\r\n\r\n",
+                ParseError::InvalidStatusLine("HTTP/1.1 2000 OK".to_string()),
+            ),
+            (
+                b"HTTP/1.1 099 Low\r\n\r\n",
+                ParseError::InvalidStatusLine("HTTP/1.1 099 Low".to_string()),
+            ),
+            (
+                b"HTTP/2 200 OK\r\n\r\n",
+                ParseError::UnsupportedVersion("HTTP/2".to_string()),
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nabc",
+                ParseError::UnexpectedEnd,
+            ),
+            (
+                b"HTTP/1.1 200 OK\r\nContent-Length: 1\r\n\r\nabc",
+                ParseError::TrailingData(2),
+            ),
+            (
+                b"HTTP/1.1 304 Not Modified\r\n\r\nabc",
+                ParseError::TrailingData(3),
+            ),
+        ];
+        for (bytes, error) in cases {
+            assert_eq!(Err(error), parse(bytes));
+        }
+    }
+
+    fn header() -> impl Strategy<Value = (String, String)> {
+        ("[A-Za-z][A-Za-z0-9-]{0,15}", "[ -~]{0,20}")
+            .prop_map(|(name, value)| (name, value.trim().to_string()))
+            .prop_filter("framing headers change the body", |(name, _)| {
+                !name.eq_ignore_ascii_case("Content-Length")
+                    && !name.eq_ignore_ascii_case("Transfer-Encoding")
+            })
+    }
+
+    fn with_headers(
+        code: u16,
+        reason: &str,
+        headers: &[(String, String)],
+    ) -> HttpResponse<Headers> {
+        let mut response = HttpResponse::default()
+            .status_line(StatusCode::new(code).unwrap(), reason)
+            .unwrap();
+        for (name, value) in headers {
+            response = response.header(name, value).unwrap();
+        }
+        response
+    }
+
+    proptest! {
+        #[test]
+        fn round_trip_with_body(
+            code in (200u16..600).prop_filter("no body allowed", |code| *code != 204 && *code != 304),
+            reason in "[A-Za-z ]{0,20}",
+            headers in prop::collection::vec(header(), 0..8),
+            body in prop::collection::vec(any::<u8>(), 0..256),
+        ) {
+            let mut sent = Vec::new();
+            with_headers(code, &reason, &headers)
+                .body(body.clone())
+                .send(&mut sent)
+                .unwrap();
+            let response = with_headers(code, &reason, &headers).body(body);
+            prop_assert_eq!(Ok(ParsedResponse::Body(response)), parse(&sent));
+        }
+
+        #[test]
+        fn round_trip_without_body(
+            code in 100u16..600,
+            reason in "[A-Za-z ]{0,20}",
+            headers in prop::collection::vec(header(), 0..8),
+        ) {
+            let mut sent = Vec::new();
+            with_headers(code, &reason, &headers).send(&mut sent).unwrap();
+            let parsed = parse(&sent);
+            prop_assert_eq!(Ok(ParsedResponse::Headers(with_headers(code, &reason, &headers))), parsed);
+        }
+    }
+}
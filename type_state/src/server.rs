@@ -0,0 +1,643 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+
+use crate::html::{Body, Headers, HttpResponse, RoutableState};
+use crate::request::{
+    HttpRequest, Limits, Method, ParseError, RequestBody, RequestHeaders, RequestLine,
+    RequestParser, Step, Version,
+};
+use crate::response::has_no_body;
+use crate::status::StatusCode;
+
+pub struct ServerConfig {
+    pub limits: Limits,
+    /// keep-alive connections are closed after this many requests
+    pub max_requests_per_connection: usize,
+    /// how many requests received in one read are answered, if a client
+    /// pipelines more the connection is closed after the last answered one
+    pub max_pipelined: usize,
+    /// how long a connection may wait for the next bytes of a request
+    pub idle_timeout: Duration,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            limits: Limits::default(),
+            max_requests_per_connection: 100,
+            max_pipelined: 16,
+            idle_timeout: Duration::from_secs(5),
+        }
+    }
+}
+
+// A routed response with its state erased, so one router can hold handlers
+// that return responses with different bodies.
+trait Reply: Send {
+    fn headers_mut(&mut self) -> &mut Headers;
+
+    fn send<'a>(
+        self: Box<Self>,
+        socket: &'a mut TcpStream,
+        with_body: bool,
+    ) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>>;
+}
+
+impl<S> Reply for HttpResponse<S>
+where
+    S: RoutableState + Send + Sync + 'static,
+{
+    fn headers_mut(&mut self) -> &mut Headers {
+        HttpResponse::headers_mut(self)
+    }
+
+    fn send<'a>(
+        self: Box<Self>,
+        socket: &'a mut TcpStream,
+        with_body: bool,
+    ) -> Pin<Box<dyn Future<Output = io::Result<()>> + Send + 'a>> {
+        Box::pin(async move {
+            if with_body {
+                self.send_async(socket).await
+            } else {
+                self.send_head_async(socket).await
+            }
+        })
+    }
+}
+
+type Handler = Box<dyn Fn(&HttpRequest) -> Box<dyn Reply> + Send + Sync>;
+
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<(Method, String, Handler)>,
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router::default()
+    }
+
+    /// Routes requests for `path` to `handler`. The handler has to return a
+    /// response in a sendable state, so forgetting the status line does not
+    /// compile:
+    ///
+    /// ```compile_fail
+    /// use type_state::{HttpResponse, Method, Router};
+    ///
+    /// Router::new().route(Method::Get, "/", |_| HttpResponse::default());
+    /// ```
+    ///
+    /// Neither does returning a streamed response, it was already written to
+    /// its own writer and nothing of it would reach the client:
+    ///
+    /// ```compile_fail
+    /// use type_state::{HttpResponse, Method, Router, StatusCode};
+    ///
+    /// Router::new().route(Method::Get, "/", |_| {
+    ///     let response = HttpResponse::default().status(StatusCode::OK);
+    ///     response.stream(Vec::new()).unwrap().finish().unwrap()
+    /// });
+    /// ```
+    ///
+    /// A GET route also answers HEAD requests, with the head only.
+    pub fn route<S, F>(mut self, method: Method, path: &str, handler: F) -> Router
+    where
+        S: RoutableState + Send + Sync + 'static,
+        F: Fn(&HttpRequest) -> HttpResponse<S> + Send + Sync + 'static,
+    {
+        let handler = move |request: &HttpRequest| Box::new(handler(request)) as Box<dyn Reply>;
+        self.routes
+            .push((method, path.to_string(), Box::new(handler)));
+        self
+    }
+
+    fn respond(&self, request: &HttpRequest) -> Box<dyn Reply> {
+        let path = request.target().path();
+        let mut allowed = Vec::new();
+        let mut get = None;
+        for (method, route, handler) in &self.routes {
+            if path != Some(route.as_str()) {
+                continue;
+            }
+            if *method == request.method() {
+                return handler(request);
+            }
+            if *method == Method::Get {
+                get = Some(handler);
+            }
+            allowed.push(method.as_str());
+        }
+        if let Some(get) = get.filter(|_| request.method() == Method::Head) {
+            return get(request);
+        }
+        if allowed.is_empty() {
+            return Box::new(text_response(StatusCode::NOT_FOUND, "not found"));
+        }
+        Box::new(
+            HttpResponse::default()
+                .status(StatusCode::METHOD_NOT_ALLOWED)
+                .set_header("Allow", &allowed.join(", "))
+                .expect("method names are valid header values")
+                .body("method not allowed"),
+        )
+    }
+}
+
+fn text_response(code: StatusCode, text: &str) -> HttpResponse<Body<String>> {
+    HttpResponse::default()
+        .status(code)
+        .content_type("text/plain; charset=utf-8")
+        .expect("valid content type")
+        .body(text.to_string())
+}
+
+fn error_status(error: &ParseError) -> StatusCode {
+    match error {
+        ParseError::RequestLineTooLong => StatusCode::URI_TOO_LONG,
+        ParseError::TooManyHeaders | ParseError::HeadersTooLarge => {
+            StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE
+        }
+        ParseError::BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+        ParseError::UnknownMethod(_) | ParseError::UnsupportedTransferEncoding(_) => {
+            StatusCode::NOT_IMPLEMENTED
+        }
+        ParseError::UnsupportedVersion(_) => StatusCode::HTTP_VERSION_NOT_SUPPORTED,
+        _ => StatusCode::BAD_REQUEST,
+    }
+}
+
+fn has_token(value: Option<&str>, token: &str) -> bool {
+    value.is_some_and(|value| {
+        value
+            .split(',')
+            .any(|t| t.trim().eq_ignore_ascii_case(token))
+    })
+}
+
+fn wants_keep_alive(request: &HttpRequest) -> bool {
+    let connection = request.header("Connection");
+    match request.version() {
+        Version::Http10 => has_token(connection, "keep-alive"),
+        Version::Http11 => !has_token(connection, "close"),
+    }
+}
+
+// Tells the client whether the connection stays open, unless the handler
+// already did. A response without a body gets `Content-Length: 0`, otherwise
+// the client could only tell where it ends when the connection is closed.
+// Returns false if the connection has to be closed.
+fn finish_head(headers: &mut Headers, version: Version, keep_alive: bool) -> bool {
+    if headers.get("Content-Length").is_none()
+        && headers.get("Transfer-Encoding").is_none()
+        && !has_no_body(headers.status_code())
+    {
+        headers.set("Content-Length", "0".to_string());
+    }
+    if let Some(connection) = headers.get("Connection") {
+        return keep_alive && !has_token(Some(connection), "close");
+    }
+    match (keep_alive, version) {
+        (false, _) => headers.set("Connection", "close".to_string()),
+        (true, Version::Http10) => headers.set("Connection", "keep-alive".to_string()),
+        (true, Version::Http11) => {}
+    }
+    keep_alive
+}
+
+// the request parser in whatever state it is, so it can be kept across reads
+enum Parsing {
+    Line(RequestParser<RequestLine>),
+    Headers(RequestParser<RequestHeaders>),
+    Body(RequestParser<RequestBody>),
+}
+
+impl Parsing {
+    fn feed(
+        self,
+        mut bytes: &[u8],
+    ) -> Result<Step<Parsing, (HttpRequest, RequestParser<RequestLine>)>, ParseError> {
+        let mut parsing = self;
+        loop {
+            parsing = match parsing {
+                Parsing::Line(parser) => match parser.feed(bytes)? {
+                    Step::Pending(parser) => return Ok(Step::Pending(Parsing::Line(parser))),
+                    Step::Done(parser) => Parsing::Headers(parser),
+                },
+                Parsing::Headers(parser) => match parser.feed(bytes)? {
+                    Step::Pending(parser) => return Ok(Step::Pending(Parsing::Headers(parser))),
+                    Step::Done(parser) => Parsing::Body(parser),
+                },
+                Parsing::Body(parser) => {
+                    return Ok(match parser.feed(bytes)? {
+                        Step::Pending(parser) => Step::Pending(Parsing::Body(parser)),
+                        Step::Done(done) => Step::Done(done),
+                    });
+                }
+            };
+            bytes = &[];
+        }
+    }
+
+    // between requests, nothing of the next one received yet
+    fn is_idle(&self) -> bool {
+        matches!(self, Parsing::Line(parser) if parser.buffered() == 0)
+    }
+}
+
+async fn serve_connection(
+    mut socket: TcpStream,
+    router: Arc<Router>,
+    config: Arc<ServerConfig>,
+    mut stop: watch::Receiver<bool>,
+) -> io::Result<()> {
+    let mut parsing = Parsing::Line(RequestParser::new(config.limits));
+    let mut buf = vec![0; 4096];
+    let mut received = 0;
+    let mut served = 0;
+    let mut pipelined = 0;
+    loop {
+        let step = parsing.feed(&buf[..received]);
+        received = 0;
+        let (request, next) = match step {
+            Ok(Step::Done(done)) => done,
+            Ok(Step::Pending(pending)) => {
+                parsing = pending;
+                if parsing.is_idle() && *stop.borrow() {
+                    return Ok(());
+                }
+                received = tokio::select! {
+                    read = socket.read(&mut buf) => read?,
+                    _ = stop.changed(), if parsing.is_idle() => return Ok(()),
+                    _ = tokio::time::sleep(config.idle_timeout) => return Ok(()),
+                };
+                if received == 0 {
+                    return Ok(());
+                }
+                pipelined = 0;
+                continue;
+            }
+            Err(error) => {
+                let mut response = text_response(error_status(&error), &error.to_string());
+                finish_head(response.headers_mut(), Version::Http11, false);
+                response.send_async(&mut socket).await?;
+                return socket.shutdown().await;
+            }
+        };
+        served += 1;
+        pipelined += 1;
+        let keep_alive = wants_keep_alive(&request)
+            && served < config.max_requests_per_connection
+            && !(pipelined >= config.max_pipelined && next.buffered() > 0)
+            && !*stop.borrow();
+
+        let mut response = router.respond(&request);
+        let keep_alive = finish_head(response.headers_mut(), request.version(), keep_alive);
+        let with_body = request.method() != Method::Head;
+        response.send(&mut socket, with_body).await?;
+        if !keep_alive {
+            return socket.shutdown().await;
+        }
+        parsing = Parsing::Line(next);
+    }
+}
+
+/// Serves `router` on `listener` until `shutdown` completes. Then no more
+/// connections are accepted, idle connections are closed and busy ones after
+/// their current response. Returns once all connections are closed.
+pub async fn serve(
+    listener: TcpListener,
+    router: Router,
+    config: ServerConfig,
+    shutdown: impl Future<Output = ()>,
+) -> io::Result<()> {
+    let router = Arc::new(router);
+    let config = Arc::new(config);
+    let (stop, stopped) = watch::channel(false);
+    let mut connections = JoinSet::new();
+    tokio::pin!(shutdown);
+    loop {
+        tokio::select! {
+            _ = &mut shutdown => break,
+            accepted = listener.accept() => {
+                if let Ok((socket, _)) = accepted {
+                    connections.spawn(serve_connection(
+                        socket,
+                        router.clone(),
+                        config.clone(),
+                        stopped.clone(),
+                    ));
+                }
+            }
+            Some(_) = connections.join_next(), if !connections.is_empty() => {}
+        }
+    }
+    stop.send_replace(true);
+    while connections.join_next().await.is_some() {}
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::sync::oneshot;
+    use tokio::task::JoinHandle;
+
+    use super::{Router, ServerConfig, serve};
+    use crate::html::HttpResponse;
+    use crate::request::Method;
+    use crate::status::StatusCode;
+
+    struct TestServer {
+        address: SocketAddr,
+        shutdown: oneshot::Sender<()>,
+        server: JoinHandle<io::Result<()>>,
+    }
+
+    async fn start(config: ServerConfig) -> TestServer {
+        let router = Router::new()
+            .route(Method::Get, "/hello", |_| {
+                HttpResponse::default().status(StatusCode::OK).body("hello")
+            })
+            .route(Method::Post, "/echo", |request| {
+                HttpResponse::default()
+                    .status(StatusCode::OK)
+                    .body(request.body().to_vec())
+            })
+            .route(Method::Delete, "/hello", |_| {
+                HttpResponse::default().status(StatusCode::NO_CONTENT)
+            })
+            .route(Method::Get, "/empty", |_| {
+                HttpResponse::default().status(StatusCode::OK)
+            });
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (shutdown, stopped) = oneshot::channel();
+        let server = tokio::spawn(serve(listener, router, config, async {
+            let _ = stopped.await;
+        }));
+        TestServer {
+            address,
+            shutdown,
+            server,
+        }
+    }
+
+    // reads one response whose body is delimited by Content-Length
+    async fn read_response(socket: &mut TcpStream) -> String {
+        let mut response = Vec::new();
+        let mut byte = [0];
+        while !response.ends_with(b"\r\n\r\n") {
+            socket.read_exact(&mut byte).await.unwrap();
+            response.push(byte[0]);
+        }
+        let head = String::from_utf8(response.clone()).unwrap();
+        let length = head
+            .lines()
+            .find_map(|line| line.strip_prefix("Content-Length: "))
+            .map_or(0, |length| length.parse().unwrap());
+        let mut body = vec![0; length];
+        socket.read_exact(&mut body).await.unwrap();
+        response.extend(body);
+        String::from_utf8(response).unwrap()
+    }
+
+    async fn assert_closed(socket: &mut TcpStream) {
+        let mut rest = Vec::new();
+        socket.read_to_end(&mut rest).await.unwrap();
+        assert_eq!("", String::from_utf8_lossy(&rest));
+    }
+
+    #[tokio::test]
+    async fn routes_by_method_and_path() {
+        let server = start(ServerConfig::default()).await;
+        let mut socket = TcpStream::connect(server.address).await.unwrap();
+        socket
+            .write_all(b"GET /hello HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\n\r\nhello",
+            read_response(&mut socket).await
+        );
+        socket
+            .write_all(b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\n\r\nblub")
+            .await
+            .unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: 4\r\n\r\nblub",
+            read_response(&mut socket).await
+        );
+        socket
+            .write_all(b"PUT /hello?x=1 HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            "HTTP/1.1 405 Method Not Allowed\r\nAllow: GET, DELETE\r\n\
+             Content-Type: text/plain; charset=utf-8\r\nContent-Length: 18\r\n\r\nmethod not allowed",
+            read_response(&mut socket).await
+        );
+        socket
+            .write_all(b"GET /nope HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\n\
+             Content-Length: 9\r\nConnection: close\r\n\r\nnot found",
+            read_response(&mut socket).await
+        );
+        assert_closed(&mut socket).await;
+    }
+
+    #[tokio::test]
+    async fn head_sends_the_head_of_get() {
+        let server = start(ServerConfig::default()).await;
+        let mut socket = TcpStream::connect(server.address).await.unwrap();
+        socket
+            .write_all(b"HEAD /hello HTTP/1.1\r\n\r\nHEAD /empty HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        // the body would be read as the start of the next response
+        let mut heads = vec![0; 117];
+        socket.read_exact(&mut heads).await.unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\n\r\n\
+             HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            String::from_utf8_lossy(&heads)
+        );
+        socket
+            .write_all(b"HEAD /echo HTTP/1.1\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut rest = String::new();
+        socket.read_to_string(&mut rest).await.unwrap();
+        assert!(rest.starts_with("HTTP/1.1 405 Method Not Allowed\r\nAllow: POST\r\n"));
+        assert!(rest.ends_with("Connection: close\r\n\r\n"));
+    }
+
+    #[tokio::test]
+    async fn keep_alive_after_response_without_body() {
+        let server = start(ServerConfig::default()).await;
+        let mut socket = TcpStream::connect(server.address).await.unwrap();
+        socket
+            .write_all(b"GET /empty HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n",
+            read_response(&mut socket).await
+        );
+        socket
+            .write_all(b"GET /empty HTTP/1.0\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            read_response(&mut socket).await
+        );
+        assert_closed(&mut socket).await;
+    }
+
+    #[tokio::test]
+    async fn http_1_0_keep_alive() {
+        let server = start(ServerConfig::default()).await;
+        let mut socket = TcpStream::connect(server.address).await.unwrap();
+        socket
+            .write_all(b"GET /hello HTTP/1.0\r\nConnection: keep-alive\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\nConnection: keep-alive\r\n\r\nhello",
+            read_response(&mut socket).await
+        );
+        socket
+            .write_all(b"GET /hello HTTP/1.0\r\n\r\n")
+            .await
+            .unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+            read_response(&mut socket).await
+        );
+        assert_closed(&mut socket).await;
+    }
+
+    #[tokio::test]
+    async fn limits_requests_per_connection() {
+        let server = start(ServerConfig {
+            max_requests_per_connection: 2,
+            ..Default::default()
+        })
+        .await;
+        let mut socket = TcpStream::connect(server.address).await.unwrap();
+        for connection in ["", "Connection: close\r\n"] {
+            socket
+                .write_all(b"DELETE /hello HTTP/1.1\r\n\r\n")
+                .await
+                .unwrap();
+            assert_eq!(
+                format!("HTTP/1.1 204 No Content\r\n{connection}\r\n"),
+                read_response(&mut socket).await
+            );
+        }
+        assert_closed(&mut socket).await;
+    }
+
+    #[tokio::test]
+    async fn limits_pipelined_requests() {
+        let server = start(ServerConfig {
+            max_pipelined: 2,
+            ..Default::default()
+        })
+        .await;
+        let mut socket = TcpStream::connect(server.address).await.unwrap();
+        socket
+            .write_all(&b"GET /hello HTTP/1.1\r\n\r\n".repeat(3))
+            .await
+            .unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\n\r\nhello",
+            read_response(&mut socket).await
+        );
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+            read_response(&mut socket).await
+        );
+        assert_closed(&mut socket).await;
+    }
+
+    #[tokio::test]
+    async fn answers_parse_errors_and_closes() {
+        let server = start(ServerConfig::default()).await;
+        for (request, status) in [
+            (
+                &b"GET /hello HTTP/1.1\r\nbad header\r\n\r\n"[..],
+                "400 Bad Request",
+            ),
+            (b"BREW /pot HTTP/1.1\r\n\r\n", "501 Not Implemented"),
+            (
+                b"GET /hello HTTP/2.0\r\n\r\n",
+                "505 HTTP Version Not Supported",
+            ),
+        ] {
+            let mut socket = TcpStream::connect(server.address).await.unwrap();
+            socket.write_all(request).await.unwrap();
+            let response = read_response(&mut socket).await;
+            assert!(response.starts_with(&format!("HTTP/1.1 {status}\r\n")));
+            assert!(response.contains("Connection: close\r\n"));
+            assert_closed(&mut socket).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn closes_idle_connections() {
+        let server = start(ServerConfig {
+            idle_timeout: Duration::from_millis(20),
+            ..Default::default()
+        })
+        .await;
+        let mut socket = TcpStream::connect(server.address).await.unwrap();
+        socket.write_all(b"GET /hel").await.unwrap();
+        assert_closed(&mut socket).await;
+    }
+
+    #[tokio::test]
+    async fn graceful_shutdown() {
+        let server = start(ServerConfig::default()).await;
+        let mut idle = TcpStream::connect(server.address).await.unwrap();
+        let mut busy = TcpStream::connect(server.address).await.unwrap();
+        idle.write_all(b"GET /hello HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        read_response(&mut idle).await;
+        busy.write_all(b"POST /echo HTTP/1.1\r\nContent-Length: 4\r\n\r\nbl")
+            .await
+            .unwrap();
+        // let the server read the partial request before it is stopped
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        server.shutdown.send(()).unwrap();
+        assert_closed(&mut idle).await;
+
+        busy.write_all(b"ub").await.unwrap();
+        assert_eq!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/octet-stream\r\nContent-Length: 4\r\nConnection: close\r\n\r\nblub",
+            read_response(&mut busy).await
+        );
+        assert_closed(&mut busy).await;
+        server.server.await.unwrap().unwrap();
+        assert!(TcpStream::connect(server.address).await.is_err());
+    }
+}
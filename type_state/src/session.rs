@@ -0,0 +1,138 @@
+use std::io::{self, ErrorKind};
+use std::marker::PhantomData;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+pub mod adder;
+
+/// Most bytes `Chan::recv` buffers for one message, a peer that sends more
+/// without completing it gets `InvalidData`.
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// Something that can be sent over a `Chan`.
+pub trait Message: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+
+    /// Parses a message from the start of `buf`, with the number of bytes it
+    /// used, or `None` if more input is needed.
+    fn decode(buf: &[u8]) -> io::Result<Option<(Self, usize)>>;
+}
+
+/// Protocol step: send a `T`, then continue with `P`.
+pub struct Tx<T, P>(PhantomData<(T, P)>);
+
+/// Protocol step: receive a `T`, then continue with `P`.
+pub struct Rx<T, P>(PhantomData<(T, P)>);
+
+/// The protocol is done.
+pub struct End;
+
+/// A protocol, `Dual` is the same protocol seen from the other side.
+pub trait Session {
+    type Dual: Session;
+}
+
+impl<T: Message, P: Session> Session for Tx<T, P> {
+    type Dual = Rx<T, P::Dual>;
+}
+
+impl<T: Message, P: Session> Session for Rx<T, P> {
+    type Dual = Tx<T, P::Dual>;
+}
+
+impl Session for End {
+    type Dual = End;
+}
+
+/// A connection that is at step `P` of a protocol, each step consumes the
+/// channel and returns it at the next step.
+pub struct Chan<P, IO> {
+    io: IO,
+    // received but not yet decoded
+    buf: Vec<u8>,
+    protocol: PhantomData<P>,
+}
+
+impl<P: Session, IO: AsyncRead + AsyncWrite + Unpin> Chan<P, IO> {
+    pub fn new(io: IO) -> Self {
+        Chan {
+            io,
+            buf: Vec::new(),
+            protocol: PhantomData,
+        }
+    }
+
+    fn next<Q>(self) -> Chan<Q, IO> {
+        Chan {
+            io: self.io,
+            buf: self.buf,
+            protocol: PhantomData,
+        }
+    }
+}
+
+impl<T: Message, P: Session, IO: AsyncRead + AsyncWrite + Unpin> Chan<Tx<T, P>, IO> {
+    pub async fn send(mut self, message: T) -> io::Result<Chan<P, IO>> {
+        let mut out = Vec::new();
+        message.encode(&mut out);
+        self.io.write_all(&out).await?;
+        self.io.flush().await?;
+        Ok(self.next())
+    }
+}
+
+impl<T: Message, P: Session, IO: AsyncRead + AsyncWrite + Unpin> Chan<Rx<T, P>, IO> {
+    pub async fn recv(mut self) -> io::Result<(T, Chan<P, IO>)> {
+        loop {
+            if let Some((message, used)) = T::decode(&self.buf)? {
+                self.buf.drain(..used);
+                return Ok((message, self.next()));
+            }
+            if self.buf.len() >= MAX_MESSAGE_SIZE {
+                return Err(io::Error::new(ErrorKind::InvalidData, "message too large"));
+            }
+            if self.io.read_buf(&mut self.buf).await? == 0 {
+                return Err(ErrorKind::UnexpectedEof.into());
+            }
+        }
+    }
+}
+
+impl<IO> Chan<End, IO> {
+    /// Returns the connection, e.g. to run the protocol again. Bytes that
+    /// were received but not used are returned too.
+    pub fn close(self) -> (IO, Vec<u8>) {
+        (self.io, self.buf)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::ErrorKind;
+
+    use tokio::io::AsyncWriteExt;
+
+    use super::adder::Number;
+    use super::{Chan, End, MAX_MESSAGE_SIZE, Rx};
+
+    #[tokio::test]
+    async fn recv_caps_the_message_size() {
+        let (server, mut client) = tokio::io::duplex(1024);
+        // a number that never ends, sent until the server hangs up
+        let client = tokio::spawn(async move {
+            let digits = [b'1'; 1024];
+            let mut sent = 0;
+            while client.write_all(&digits).await.is_ok() {
+                sent += digits.len();
+            }
+            sent
+        });
+        let error = Chan::<Rx<Number, End>, _>::new(server)
+            .recv()
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(ErrorKind::InvalidData, error.kind());
+        assert!(client.await.unwrap() <= MAX_MESSAGE_SIZE + 2048);
+    }
+}
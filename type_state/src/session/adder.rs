@@ -0,0 +1,180 @@
+use std::io::{self, ErrorKind};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+
+use super::{Chan, End, Message, Rx, Session, Tx};
+
+/// The prompt for a number, `< x = `.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Prompt(pub String);
+
+/// A number on its own line, as typed by the client.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Number(pub i64);
+
+/// The result, `> z = 3`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Sum(pub i64);
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+fn line(buf: &[u8]) -> io::Result<Option<(&str, usize)>> {
+    let Some(end) = buf.iter().position(|&b| b == b'\n') else {
+        return Ok(None);
+    };
+    let line = std::str::from_utf8(&buf[..end]).map_err(|_| invalid("line is not UTF-8"))?;
+    Ok(Some((line.trim(), end + 1)))
+}
+
+impl Message for Prompt {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(format!("< {} = ", self.0).as_bytes());
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Option<(Self, usize)>> {
+        let Some(end) = buf.windows(3).position(|w| w == b" = ") else {
+            return Ok(None);
+        };
+        let name = buf[..end]
+            .strip_prefix(b"< ")
+            .and_then(|name| std::str::from_utf8(name).ok())
+            .ok_or_else(|| invalid("expected a prompt"))?;
+        Ok(Some((Prompt(name.to_string()), end + 3)))
+    }
+}
+
+impl Message for Number {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(format!("{}\n", self.0).as_bytes());
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Option<(Self, usize)>> {
+        let Some((line, used)) = line(buf)? else {
+            return Ok(None);
+        };
+        let n = line.parse().map_err(|_| invalid("expected a number"))?;
+        Ok(Some((Number(n), used)))
+    }
+}
+
+impl Message for Sum {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(format!("> z = {}\n", self.0).as_bytes());
+    }
+
+    fn decode(buf: &[u8]) -> io::Result<Option<(Self, usize)>> {
+        let Some((line, used)) = line(buf)? else {
+            return Ok(None);
+        };
+        let n = line
+            .strip_prefix("> z = ")
+            .and_then(|n| n.parse().ok())
+            .ok_or_else(|| invalid("expected a sum"))?;
+        Ok(Some((Sum(n), used)))
+    }
+}
+
+/// The adder conversation of `async_io`, as seen by the server: prompt for
+/// x, read x, prompt for y, read y, send the sum.
+///
+/// Sending the sum before reading y does not compile:
+///
+/// ```compile_fail
+/// use type_state::session::Chan;
+/// use type_state::session::adder::{AdderServer, Number, Prompt, Sum};
+///
+/// async fn reply_early(chan: Chan<AdderServer, tokio::io::DuplexStream>) -> std::io::Result<()> {
+///     let chan = chan.send(Prompt("x".to_string())).await?;
+///     let (Number(x), chan) = chan.recv().await?;
+///     let chan = chan.send(Prompt("y".to_string())).await?;
+///     chan.send(Sum(x)).await?;
+///     Ok(())
+/// }
+/// ```
+pub type AdderServer = Tx<Prompt, Rx<Number, Tx<Prompt, Rx<Number, Tx<Sum, End>>>>>;
+
+/// The adder conversation as seen by the client.
+pub type AdderClient = <AdderServer as Session>::Dual;
+
+/// Runs the adder conversation once and returns the connection. A sum that
+/// does not fit into an `i64` is `InvalidData`, like an invalid number.
+pub async fn serve_adder<IO: AsyncRead + AsyncWrite + Unpin>(
+    chan: Chan<AdderServer, IO>,
+) -> io::Result<Chan<End, IO>> {
+    let chan = chan.send(Prompt("x".to_string())).await?;
+    let (Number(x), chan) = chan.recv().await?;
+    let chan = chan.send(Prompt("y".to_string())).await?;
+    let (Number(y), chan) = chan.recv().await?;
+    let z = x
+        .checked_add(y)
+        .ok_or_else(|| invalid("sum out of range"))?;
+    chan.send(Sum(z)).await
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::{AdderClient, AdderServer, Number, Prompt, Sum, serve_adder};
+    use crate::session::Chan;
+
+    #[tokio::test]
+    async fn adder_conversation() {
+        let (server, client) = tokio::io::duplex(64);
+        let server = tokio::spawn(serve_adder(Chan::<AdderServer, _>::new(server)));
+
+        let chan = Chan::<AdderClient, _>::new(client);
+        let (prompt, chan) = chan.recv().await.unwrap();
+        assert_eq!(Prompt("x".to_string()), prompt);
+        let chan = chan.send(Number(2)).await.unwrap();
+        let (prompt, chan) = chan.recv().await.unwrap();
+        assert_eq!(Prompt("y".to_string()), prompt);
+        let chan = chan.send(Number(-5)).await.unwrap();
+        let (sum, chan) = chan.recv().await.unwrap();
+        assert_eq!(Sum(-3), sum);
+        let (_client, rest) = chan.close();
+        assert!(rest.is_empty());
+
+        let (_server, rest) = server.await.unwrap().unwrap().close();
+        assert!(rest.is_empty());
+    }
+
+    #[tokio::test]
+    async fn same_bytes_as_async_io() {
+        let (server, mut client) = tokio::io::duplex(64);
+        let server = tokio::spawn(serve_adder(Chan::<AdderServer, _>::new(server)));
+        // typed ahead, both numbers arrive in one read
+        client.write_all(b"40\n 2 \n").await.unwrap();
+        let mut received = Vec::new();
+        let expected = b"< x = < y = > z = 42\n";
+        while received.len() < expected.len() {
+            client.read_buf(&mut received).await.unwrap();
+        }
+        assert_eq!(&expected[..], &received[..]);
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn invalid_number() {
+        let (server, mut client) = tokio::io::duplex(64);
+        client.write_all(b"four\n").await.unwrap();
+        let error = serve_adder(Chan::<AdderServer, _>::new(server))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(std::io::ErrorKind::InvalidData, error.kind());
+    }
+
+    #[tokio::test]
+    async fn overflowing_sum() {
+        let (server, mut client) = tokio::io::duplex(64);
+        client.write_all(b"9223372036854775807\n1\n").await.unwrap();
+        let error = serve_adder(Chan::<AdderServer, _>::new(server))
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(std::io::ErrorKind::InvalidData, error.kind());
+    }
+}
@@ -0,0 +1,214 @@
+use std::error::Error;
+use std::fmt::Display;
+
+/// An HTTP status code in the range 100..=599.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StatusCode(u16);
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InvalidStatusCode(pub u16);
+
+impl Display for InvalidStatusCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid status code {}, expected 100..=599", self.0)
+    }
+}
+
+impl Error for InvalidStatusCode {}
+
+impl StatusCode {
+    pub const CONTINUE: StatusCode = StatusCode(100);
+    pub const SWITCHING_PROTOCOLS: StatusCode = StatusCode(101);
+    pub const OK: StatusCode = StatusCode(200);
+    pub const CREATED: StatusCode = StatusCode(201);
+    pub const ACCEPTED: StatusCode = StatusCode(202);
+    pub const NO_CONTENT: StatusCode = StatusCode(204);
+    pub const MOVED_PERMANENTLY: StatusCode = StatusCode(301);
+    pub const FOUND: StatusCode = StatusCode(302);
+    pub const NOT_MODIFIED: StatusCode = StatusCode(304);
+    pub const BAD_REQUEST: StatusCode = StatusCode(400);
+    pub const UNAUTHORIZED: StatusCode = StatusCode(401);
+    pub const FORBIDDEN: StatusCode = StatusCode(403);
+    pub const NOT_FOUND: StatusCode = StatusCode(404);
+    pub const METHOD_NOT_ALLOWED: StatusCode = StatusCode(405);
+    pub const REQUEST_TIMEOUT: StatusCode = StatusCode(408);
+    pub const PAYLOAD_TOO_LARGE: StatusCode = StatusCode(413);
+    pub const URI_TOO_LONG: StatusCode = StatusCode(414);
+    pub const REQUEST_HEADER_FIELDS_TOO_LARGE: StatusCode = StatusCode(431);
+    pub const INTERNAL_SERVER_ERROR: StatusCode = StatusCode(500);
+    pub const NOT_IMPLEMENTED: StatusCode = StatusCode(501);
+    pub const SERVICE_UNAVAILABLE: StatusCode = StatusCode(503);
+    pub const HTTP_VERSION_NOT_SUPPORTED: StatusCode = StatusCode(505);
+
+    pub const fn new(code: u16) -> Result<StatusCode, InvalidStatusCode> {
+        match code {
+            100..=599 => Ok(StatusCode(code)),
+            _ => Err(InvalidStatusCode(code)),
+        }
+    }
+
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    /// The reason phrase registered for this code, `None` for unassigned
+    /// codes.
+    pub const fn canonical_reason(self) -> Option<&'static str> {
+        Some(match self.0 {
+            100 => "Continue",
+            101 => "Switching Protocols",
+            102 => "Processing",
+            103 => "Early Hints",
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            203 => "Non-Authoritative Information",
+            204 => "No Content",
+            205 => "Reset Content",
+            206 => "Partial Content",
+            207 => "Multi-Status",
+            208 => "Already Reported",
+            226 => "IM Used",
+            300 => "Multiple Choices",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            305 => "Use Proxy",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            402 => "Payment Required",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            406 => "Not Acceptable",
+            407 => "Proxy Authentication Required",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            410 => "Gone",
+            411 => "Length Required",
+            412 => "Precondition Failed",
+            413 => "Content Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            417 => "Expectation Failed",
+            418 => "I'm a teapot",
+            421 => "Misdirected Request",
+            422 => "Unprocessable Content",
+            423 => "Locked",
+            424 => "Failed Dependency",
+            425 => "Too Early",
+            426 => "Upgrade Required",
+            428 => "Precondition Required",
+            429 => "Too Many Requests",
+            431 => "Request Header Fields Too Large",
+            451 => "Unavailable For Legal Reasons",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            505 => "HTTP Version Not Supported",
+            506 => "Variant Also Negotiates",
+            507 => "Insufficient Storage",
+            508 => "Loop Detected",
+            510 => "Not Extended",
+            511 => "Network Authentication Required",
+            _ => return None,
+        })
+    }
+
+    pub const fn is_informational(self) -> bool {
+        matches!(self.0, 100..=199)
+    }
+
+    pub const fn is_success(self) -> bool {
+        matches!(self.0, 200..=299)
+    }
+
+    pub const fn is_redirection(self) -> bool {
+        matches!(self.0, 300..=399)
+    }
+
+    pub const fn is_client_error(self) -> bool {
+        matches!(self.0, 400..=499)
+    }
+
+    pub const fn is_server_error(self) -> bool {
+        matches!(self.0, 500..=599)
+    }
+}
+
+impl TryFrom<u16> for StatusCode {
+    type Error = InvalidStatusCode;
+
+    fn try_from(code: u16) -> Result<Self, Self::Error> {
+        StatusCode::new(code)
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(code: StatusCode) -> u16 {
+        code.0
+    }
+}
+
+impl Display for StatusCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InvalidStatusCode, StatusCode};
+
+    #[test]
+    fn accepts_only_100_to_599() {
+        assert_eq!(Err(InvalidStatusCode(0)), StatusCode::new(0));
+        assert_eq!(Err(InvalidStatusCode(99)), StatusCode::new(99));
+        assert_eq!(Err(InvalidStatusCode(600)), StatusCode::new(600));
+        assert_eq!(100, StatusCode::new(100).unwrap().as_u16());
+        assert_eq!(599, StatusCode::try_from(599).unwrap().as_u16());
+    }
+
+    #[test]
+    fn codes_above_u8() {
+        assert_eq!(StatusCode::NOT_FOUND, StatusCode::new(404).unwrap());
+        assert_eq!(500, u16::from(StatusCode::INTERNAL_SERVER_ERROR));
+        assert_eq!("404", StatusCode::NOT_FOUND.to_string());
+    }
+
+    #[test]
+    fn reason_phrases() {
+        assert_eq!(Some("OK"), StatusCode::OK.canonical_reason());
+        assert_eq!(Some("Not Found"), StatusCode::NOT_FOUND.canonical_reason());
+        assert_eq!(
+            Some("Internal Server Error"),
+            StatusCode::INTERNAL_SERVER_ERROR.canonical_reason()
+        );
+        assert_eq!(None, StatusCode::new(299).unwrap().canonical_reason());
+    }
+
+    #[test]
+    fn classes() {
+        let classes = |code: u16| {
+            let code = StatusCode::new(code).unwrap();
+            [
+                code.is_informational(),
+                code.is_success(),
+                code.is_redirection(),
+                code.is_client_error(),
+                code.is_server_error(),
+            ]
+        };
+        assert_eq!([true, false, false, false, false], classes(101));
+        assert_eq!([false, true, false, false, false], classes(204));
+        assert_eq!([false, false, true, false, false], classes(399));
+        assert_eq!([false, false, false, true, false], classes(404));
+        assert_eq!([false, false, false, false, true], classes(599));
+    }
+}
@@ -0,0 +1,420 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, Ident, LitStr, PathArguments, Type,
+    parse_macro_input,
+};
+
+/// Derives a type-state builder `<Name>Builder`, created by `Name::builder()`.
+/// `build()` only exists once every required field is set, which is checked
+/// at compile time, the builder holds no `Option`s.
+///
+/// Fields are required unless annotated:
+/// - `#[builder(optional)]` starts as `Default::default()` and may be set any
+///   number of times,
+/// - `#[builder(optional, each = "name")]` additionally gets a setter `name`
+///   that adds one item to a collection like `Vec<T>`,
+/// - `#[builder(ordered)]` is required and can only be set after the previous
+///   ordered field. Other fields can only be set after the ordered field
+///   declared before them and before the ordered field declared after them.
+///
+/// Only structs with named fields and without generic parameters are
+/// supported.
+///
+/// ```
+/// use type_state_derive::TypeStateBuilder;
+///
+/// #[derive(TypeStateBuilder)]
+/// struct Request {
+///     #[builder(ordered)]
+///     method: String,
+///     #[builder(optional, each = "header")]
+///     headers: Vec<(String, String)>,
+///     #[builder(ordered)]
+///     body: Vec<u8>,
+///     timeout: u64,
+/// }
+///
+/// let request = Request::builder()
+///     .method("GET".to_string())
+///     .header(("Host".to_string(), "example.com".to_string()))
+///     .body(Vec::new())
+///     .timeout(10)
+///     .build();
+/// assert_eq!(1, request.headers.len());
+/// ```
+///
+/// `build()` is missing while a required field is unset:
+///
+/// ```compile_fail
+/// use type_state_derive::TypeStateBuilder;
+///
+/// #[derive(TypeStateBuilder)]
+/// struct Request {
+///     method: String,
+///     body: Vec<u8>,
+/// }
+///
+/// let request = Request::builder().method("GET".to_string()).build();
+/// ```
+///
+/// and a field can't be set out of order:
+///
+/// ```compile_fail
+/// use type_state_derive::TypeStateBuilder;
+///
+/// #[derive(TypeStateBuilder)]
+/// struct Request {
+///     #[builder(ordered)]
+///     method: String,
+///     #[builder(optional, each = "header")]
+///     headers: Vec<(String, String)>,
+///     #[builder(ordered)]
+///     body: Vec<u8>,
+/// }
+///
+/// let request = Request::builder()
+///     .method("GET".to_string())
+///     .body(Vec::new())
+///     .header(("Host".to_string(), "example.com".to_string()))
+///     .build();
+/// ```
+#[proc_macro_derive(TypeStateBuilder, attributes(builder))]
+pub fn derive_type_state_builder(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+enum Kind {
+    Required,
+    Ordered,
+    Optional { each: Option<Ident> },
+}
+
+struct Field {
+    name: Ident,
+    ty: Type,
+    kind: Kind,
+    // type parameter tracking whether a required field is set
+    state: Option<Ident>,
+}
+
+// what a setter requires of a type parameter
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Unset,
+    Set,
+    Any,
+}
+
+fn parse_field(field: &syn::Field) -> syn::Result<(Ident, Type, Kind)> {
+    let name = field.ident.clone().expect("named field");
+    let mut ordered = false;
+    let mut optional = false;
+    let mut each = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("builder"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("ordered") {
+                ordered = true;
+            } else if meta.path.is_ident("optional") {
+                optional = true;
+            } else if meta.path.is_ident("each") {
+                let setter: LitStr = meta.value()?.parse()?;
+                each = Some(setter.parse()?);
+            } else {
+                return Err(meta.error("expected `ordered`, `optional` or `each`"));
+            }
+            Ok(())
+        })?;
+    }
+    let kind = match (ordered, optional) {
+        (true, true) => {
+            return Err(syn::Error::new_spanned(
+                &name,
+                "a field can't be both `ordered` and `optional`",
+            ));
+        }
+        (true, false) if each.is_some() => {
+            return Err(syn::Error::new_spanned(&name, "`each` needs `optional`"));
+        }
+        (true, false) => Kind::Ordered,
+        (false, true) => Kind::Optional { each },
+        (false, false) if each.is_some() => {
+            return Err(syn::Error::new_spanned(&name, "`each` needs `optional`"));
+        }
+        (false, false) => Kind::Required,
+    };
+    Ok((name, field.ty.clone(), kind))
+}
+
+// the `T` of a collection type like `Vec<T>`
+fn item_type(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let PathArguments::AngleBracketed(arguments) = &path.path.segments.last()?.arguments else {
+        return None;
+    };
+    match arguments.args.first()? {
+        GenericArgument::Type(item) => Some(item),
+        _ => None,
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new_spanned(
+            &input.generics,
+            "generic structs are not supported",
+        ));
+    }
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "only structs can derive TypeStateBuilder",
+        ));
+    };
+    let Fields::Named(named) = &data.fields else {
+        return Err(syn::Error::new_spanned(
+            &input.ident,
+            "only structs with named fields can derive TypeStateBuilder",
+        ));
+    };
+
+    let mut fields = Vec::new();
+    for field in &named.named {
+        let (name, ty, kind) = parse_field(field)?;
+        let state = match kind {
+            Kind::Optional { .. } => None,
+            _ => Some(format_ident!("__S{}", fields.len())),
+        };
+        fields.push(Field {
+            name,
+            ty,
+            kind,
+            state,
+        });
+    }
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let builder = format_ident!("{}Builder", name);
+    let states: Vec<&Ident> = fields.iter().filter_map(|f| f.state.as_ref()).collect();
+    let field_names: Vec<&Ident> = fields.iter().map(|f| &f.name).collect();
+
+    // the builder type with every state parameter replaced as requested
+    let builder_type = |states: &dyn Fn(&Field) -> State| {
+        let arguments = fields.iter().filter_map(|field| {
+            let param = field.state.as_ref()?;
+            let ty = &field.ty;
+            Some(match states(field) {
+                State::Unset => quote!(()),
+                State::Set => quote!((#ty,)),
+                State::Any => quote!(#param),
+            })
+        });
+        quote!(#builder<#(#arguments),*>)
+    };
+    let generics = |states: &dyn Fn(&Field) -> State| {
+        let params = fields
+            .iter()
+            .filter(|field| field.state.is_some() && states(field) == State::Any)
+            .map(|field| &field.state);
+        quote!(<#(#params),*>)
+    };
+
+    let struct_fields = fields.iter().map(|field| {
+        let (name, ty) = (&field.name, &field.ty);
+        match &field.state {
+            Some(state) => quote!(#name: #state),
+            None => quote!(#name: #ty),
+        }
+    });
+    let initial = fields.iter().map(|field| {
+        let name = &field.name;
+        match field.state {
+            Some(_) => quote!(#name: ()),
+            None => quote!(#name: ::core::default::Default::default()),
+        }
+    });
+    let start = builder_type(&|_| State::Unset);
+    let done = builder_type(&|_| State::Set);
+    let built = fields.iter().map(|field| {
+        let name = &field.name;
+        match field.state {
+            Some(_) => quote!(#name: self.#name.0),
+            None => quote!(#name: self.#name),
+        }
+    });
+
+    let mut setters = Vec::new();
+    for (index, field) in fields.iter().enumerate() {
+        let ordered = |field: &&Field| matches!(field.kind, Kind::Ordered);
+        let previous = fields[..index].iter().rfind(ordered).map(|f| &f.name);
+        let next = fields[index + 1..].iter().find(ordered).map(|f| &f.name);
+        let before = |other: &Field, own: State| {
+            if other.name == field.name {
+                own
+            } else if Some(&other.name) == previous {
+                State::Set
+            } else if Some(&other.name) == next && !matches!(field.kind, Kind::Ordered) {
+                State::Unset
+            } else {
+                State::Any
+            }
+        };
+        let (setter, ty) = (&field.name, &field.ty);
+        match &field.kind {
+            Kind::Required | Kind::Ordered => {
+                let impl_generics = generics(&|other| before(other, State::Unset));
+                let from = builder_type(&|other| before(other, State::Unset));
+                let to = builder_type(&|other| before(other, State::Set));
+                let moved = field_names.iter().map(|name| {
+                    if *name == setter {
+                        quote!(#name: (value,))
+                    } else {
+                        quote!(#name: self.#name)
+                    }
+                });
+                setters.push(quote! {
+                    impl #impl_generics #from {
+                        #vis fn #setter(self, value: #ty) -> #to {
+                            #builder { #(#moved),* }
+                        }
+                    }
+                });
+            }
+            Kind::Optional { each } => {
+                let impl_generics = generics(&|other| before(other, State::Any));
+                let this = builder_type(&|other| before(other, State::Any));
+                let each = match each {
+                    Some(each) => {
+                        let item = item_type(ty).ok_or_else(|| {
+                            syn::Error::new_spanned(ty, "`each` needs a collection like `Vec<T>`")
+                        })?;
+                        quote! {
+                            #vis fn #each(mut self, item: #item) -> Self {
+                                ::core::iter::Extend::extend(
+                                    &mut self.#setter,
+                                    ::core::iter::once(item),
+                                );
+                                self
+                            }
+                        }
+                    }
+                    None => quote!(),
+                };
+                setters.push(quote! {
+                    impl #impl_generics #this {
+                        #vis fn #setter(mut self, value: #ty) -> Self {
+                            self.#setter = value;
+                            self
+                        }
+
+                        #each
+                    }
+                });
+            }
+        }
+    }
+
+    Ok(quote! {
+        #vis struct #builder<#(#states = ()),*> {
+            #(#struct_fields),*
+        }
+
+        impl #name {
+            #vis fn builder() -> #start {
+                #builder { #(#initial),* }
+            }
+        }
+
+        #(#setters)*
+
+        impl #done {
+            #vis fn build(self) -> #name {
+                #name { #(#built),* }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use syn::parse_quote;
+
+    use super::expand;
+
+    fn error(input: syn::DeriveInput) -> String {
+        expand(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn rejected_inputs() {
+        assert_eq!(
+            "generic structs are not supported",
+            error(parse_quote!(
+                struct Wrapper<T> {
+                    inner: T,
+                }
+            ))
+        );
+        assert_eq!(
+            "only structs with named fields can derive TypeStateBuilder",
+            error(parse_quote!(
+                struct Pair(u8, u8);
+            ))
+        );
+        assert_eq!(
+            "a field can't be both `ordered` and `optional`",
+            error(parse_quote!(
+                struct Both {
+                    #[builder(ordered, optional)]
+                    field: u8,
+                }
+            ))
+        );
+        assert_eq!(
+            "`each` needs a collection like `Vec<T>`",
+            error(parse_quote!(
+                struct Scalar {
+                    #[builder(optional, each = "item")]
+                    field: u8,
+                }
+            ))
+        );
+        assert_eq!(
+            "expected `ordered`, `optional` or `each`",
+            error(parse_quote!(
+                struct Unknown {
+                    #[builder(required)]
+                    field: u8,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn one_state_per_required_field() {
+        let tokens = expand(parse_quote!(
+            struct Request {
+                #[builder(ordered)]
+                method: String,
+                #[builder(optional)]
+                timeout: u64,
+                body: Vec<u8>,
+            }
+        ))
+        .unwrap()
+        .to_string();
+        assert!(tokens.starts_with("struct RequestBuilder < __S0 = () , __S2 = () >"));
+        assert!(tokens.contains("fn build (self) -> Request"));
+    }
+}